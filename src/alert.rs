@@ -0,0 +1,461 @@
+use {
+    std::{
+        collections::{HashMap, VecDeque},
+        fmt,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    async_trait::async_trait,
+    serde_json::json,
+    solana_sdk::pubkey::Pubkey,
+    anyhow::Result,
+    tracing::warn,
+    crate::{discovery::DiscoveredVault, error::CrankError, explorer::ExplorerLinks, outcome::CrankOutcome},
+};
+
+/// Most alerts delivered in any `ALERT_RATE_WINDOW`, across every kind and subject.
+pub const ALERT_RATE_LIMIT: usize = 20;
+pub const ALERT_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// How long a webhook may take to accept an alert.
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    /// A savings vault failed to crank several times in a row.
+    RepeatedFailures,
+    /// An interest depositor treasury cannot cover the interest its vaults owe.
+    TreasuryUnderfunded,
+    /// The cranker keypair is running out of SOL.
+    LowBalance,
+    /// The RPC node could not be reached.
+    RpcOutage,
+}
+
+impl fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::RepeatedFailures => "repeated crank failures",
+            Self::TreasuryUnderfunded => "interest depositor treasury underfunded",
+            Self::LowBalance => "cranker balance low",
+            Self::RpcOutage => "RPC outage",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Critical,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub severity: Severity,
+    /// What the alert is about, e.g. a savings vault or mint. Alerts of the same kind and
+    /// subject are deduplicated.
+    pub subject: String,
+    pub message: String,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "WARNING",
+            Severity::Critical => "CRITICAL",
+        };
+        write!(f, "[{}] {} for {}: {}", severity, self.kind, self.subject, self.message)
+    }
+}
+
+/// A destination alerts are delivered to.
+#[async_trait]
+pub trait AlertSink: Send + Sync {
+    async fn send(&self, alert: &Alert) -> Result<()>;
+}
+
+/// Shape of the JSON body posted by `WebhookSink`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookFormat {
+    /// `{"text": ...}`, accepted by Slack incoming webhooks and most chat integrations.
+    Slack,
+    /// `{"content": ...}`, accepted by Discord webhooks.
+    Discord,
+}
+
+/// Posts every alert as JSON to an HTTP webhook.
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    format: WebhookFormat,
+}
+
+impl WebhookSink {
+    pub fn new(url: String, format: WebhookFormat) -> Result<Self> {
+        let client = reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build()?;
+
+        Ok(Self { client, url, format })
+    }
+}
+
+#[async_trait]
+impl AlertSink for WebhookSink {
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let body = match self.format {
+            WebhookFormat::Slack => json!({ "text": alert.to_string() }),
+            WebhookFormat::Discord => json!({ "content": alert.to_string() }),
+        };
+        self.client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Delivers alerts to every sink, dropping repeats of the same kind and subject within the
+/// dedup window and anything beyond `ALERT_RATE_LIMIT` per `ALERT_RATE_WINDOW`.
+///
+/// Delivery runs on spawned tasks, so a slow webhook never holds up cranking.
+pub struct Alerter {
+    sinks: Vec<Arc<dyn AlertSink>>,
+    dedup_window: Duration,
+    failure_threshold: u32,
+    state: Mutex<AlerterState>,
+}
+
+#[derive(Default)]
+struct AlerterState {
+    last_sent: HashMap<(AlertKind, String), Instant>,
+    recent: VecDeque<Instant>,
+    consecutive_failures: HashMap<Pubkey, u32>,
+}
+
+impl Alerter {
+    pub fn new(dedup_window: Duration, failure_threshold: u32) -> Self {
+        Self {
+            sinks: Vec::new(),
+            dedup_window,
+            failure_threshold,
+            state: Mutex::new(AlerterState::default()),
+        }
+    }
+
+    pub fn add_sink(&mut self, sink: Box<dyn AlertSink>) {
+        self.sinks.push(Arc::from(sink));
+    }
+
+    /// Spawns the delivery of `alert` to every sink unless it is deduplicated or rate limited.
+    /// Sink errors are logged, never returned, so alerting cannot stop cranking.
+    pub fn alert(&self, alert: Alert) {
+        if self.sinks.is_empty() || !self.admit(&alert) {
+            return;
+        }
+        let alert = Arc::new(alert);
+        for sink in &self.sinks {
+            let sink = sink.clone();
+            let alert = alert.clone();
+            tokio::spawn(async move {
+                if let Err(err) = sink.send(&alert).await {
+                    warn!(kind = ?alert.kind, subject = %alert.subject, error = %err, "failed to deliver alert");
+                }
+            });
+        }
+    }
+
+    /// Counts consecutive failures per savings vault and alerts once `failure_threshold` is reached.
+    pub fn observe_outcome(&self, vault: &DiscoveredVault, outcome: &CrankOutcome, links: &ExplorerLinks) {
+        let failures = {
+            let mut state = match self.state.lock() {
+                Ok(state) => state,
+                Err(_) => return,
+            };
+            match outcome.error() {
+                None | Some(CrankError::AlreadyAccrued(_)) => {
+                    state.consecutive_failures.remove(&vault.savings_vault);
+                    return;
+                }
+                // Underfunded treasuries raise their own alert per mint.
+                Some(CrankError::TreasuryUnderfunded { .. }) => return,
+                Some(_) => {
+                    let failures = state.consecutive_failures.entry(vault.savings_vault).or_default();
+                    *failures += 1;
+                    *failures
+                }
+            }
+        };
+        if failures >= self.failure_threshold {
+            let link = match outcome.signature() {
+                Some(signature) => links.transaction(&signature),
+                None => links.account(&vault.savings_vault),
+            };
+            self.alert(Alert {
+                kind: AlertKind::RepeatedFailures,
+                severity: Severity::Critical,
+                subject: vault.savings_vault.to_string(),
+                message: format!(
+                    "{} consecutive failed cranks (wallet {}, mint {}), last: {} ({})",
+                    failures, vault.wallet, vault.mint, outcome, link
+                ),
+            });
+        }
+    }
+
+    fn admit(&self, alert: &Alert) -> bool {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return false,
+        };
+        let now = Instant::now();
+        let key = (alert.kind, alert.subject.clone());
+        if let Some(last_sent) = state.last_sent.get(&key) {
+            if now.duration_since(*last_sent) < self.dedup_window {
+                return false;
+            }
+        }
+        while let Some(sent_at) = state.recent.front() {
+            if now.duration_since(*sent_at) < ALERT_RATE_WINDOW {
+                break;
+            }
+            state.recent.pop_front();
+        }
+        if state.recent.len() >= ALERT_RATE_LIMIT {
+            return false;
+        }
+        state.recent.push_back(now);
+        state.last_sent.insert(key, now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        serde_json::Value,
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+            sync::mpsc::{unbounded_channel, UnboundedReceiver},
+        },
+        crate::{
+            client::DetectedCluster,
+            explorer::Explorer,
+            outcome::ProgramErrorInfo,
+            vault_state::VaultSchedule,
+        },
+    };
+
+    /// How long to wait for a webhook post before concluding none is coming.
+    const QUIET_PERIOD: Duration = Duration::from_millis(300);
+
+    /// Starts a local webhook answering 200 to every post, returning its URL and the JSON
+    /// bodies it received.
+    async fn webhook_stub() -> (String, UnboundedReceiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (sender, receiver) = unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut chunk = [0u8; 1024];
+                    let body_start = loop {
+                        if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+                            break end + 4;
+                        }
+                        let read = stream.read(&mut chunk).await.unwrap();
+                        assert!(read > 0, "connection closed before the request head ended");
+                        request.extend_from_slice(&chunk[..read]);
+                    };
+                    let head = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+                    let content_length: usize = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .map_or(0, |length| length.trim().parse().unwrap());
+                    while request.len() < body_start + content_length {
+                        let read = stream.read(&mut chunk).await.unwrap();
+                        assert!(read > 0, "connection closed before the body ended");
+                        request.extend_from_slice(&chunk[..read]);
+                    }
+                    let body = serde_json::from_slice(&request[body_start..body_start + content_length]).unwrap();
+                    let _ = sender.send(body);
+                    let response = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+                    stream.write_all(response).await.unwrap();
+                });
+            }
+        });
+        (url, receiver)
+    }
+
+    /// Every body the stub receives until it stays quiet for `QUIET_PERIOD`.
+    async fn received(receiver: &mut UnboundedReceiver<Value>) -> Vec<Value> {
+        let mut bodies = Vec::new();
+        while let Ok(Some(body)) = tokio::time::timeout(QUIET_PERIOD, receiver.recv()).await {
+            bodies.push(body);
+        }
+        bodies
+    }
+
+    async fn alerter(dedup_window: Duration, failure_threshold: u32) -> (Alerter, UnboundedReceiver<Value>) {
+        let (url, receiver) = webhook_stub().await;
+        let mut alerter = Alerter::new(dedup_window, failure_threshold);
+        alerter.add_sink(Box::new(WebhookSink::new(url, WebhookFormat::Slack).unwrap()));
+        (alerter, receiver)
+    }
+
+    fn alert(subject: &str) -> Alert {
+        Alert {
+            kind: AlertKind::LowBalance,
+            severity: Severity::Warning,
+            subject: subject.to_string(),
+            message: "0.5 SOL left".to_string(),
+        }
+    }
+
+    fn vault() -> DiscoveredVault {
+        DiscoveredVault {
+            savings_vault: Pubkey::new_unique(),
+            wallet: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            schedule: VaultSchedule {
+                last_accrued_at: 0,
+                interest_period: 0,
+            },
+            interest_rate_bps: 100,
+        }
+    }
+
+    fn links() -> ExplorerLinks {
+        ExplorerLinks::new(Explorer::Solana, Some(DetectedCluster::Devnet), "http://127.0.0.1:8899".to_string())
+    }
+
+    fn failure() -> CrankOutcome {
+        CrankOutcome::failed(None, CrankError::Timeout)
+    }
+
+    fn already_accrued() -> CrankOutcome {
+        CrankOutcome::failed(
+            None,
+            CrankError::AlreadyAccrued(ProgramErrorInfo {
+                instruction_index: 2,
+                code: 6000,
+                name: None,
+                message: None,
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn slack_posts_text() {
+        let (url, mut receiver) = webhook_stub().await;
+        let alert = alert("cranker");
+        WebhookSink::new(url, WebhookFormat::Slack).unwrap().send(&alert).await.unwrap();
+
+        assert_eq!(received(&mut receiver).await, vec![json!({ "text": alert.to_string() })]);
+    }
+
+    #[tokio::test]
+    async fn discord_posts_content() {
+        let (url, mut receiver) = webhook_stub().await;
+        let alert = alert("cranker");
+        WebhookSink::new(url, WebhookFormat::Discord).unwrap().send(&alert).await.unwrap();
+
+        assert_eq!(received(&mut receiver).await, vec![json!({ "content": alert.to_string() })]);
+    }
+
+    #[tokio::test]
+    async fn deduplicates_within_the_window() {
+        let (alerter, mut receiver) = alerter(Duration::from_secs(3600), 3).await;
+        alerter.alert(alert("cranker"));
+        alerter.alert(alert("cranker"));
+        alerter.alert(alert("other cranker"));
+
+        assert_eq!(received(&mut receiver).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sends_again_after_the_window() {
+        let (alerter, mut receiver) = alerter(Duration::from_millis(50), 3).await;
+        alerter.alert(alert("cranker"));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        alerter.alert(alert("cranker"));
+
+        assert_eq!(received(&mut receiver).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_across_subjects() {
+        let (alerter, mut receiver) = alerter(Duration::from_secs(3600), 3).await;
+        for index in 0..ALERT_RATE_LIMIT + 5 {
+            alerter.alert(alert(&format!("cranker {}", index)));
+        }
+
+        assert_eq!(received(&mut receiver).await.len(), ALERT_RATE_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn alerts_at_the_failure_threshold() {
+        let (alerter, mut receiver) = alerter(Duration::from_secs(3600), 3).await;
+        let vault = vault();
+        alerter.observe_outcome(&vault, &failure(), &links());
+        alerter.observe_outcome(&vault, &failure(), &links());
+        assert!(received(&mut receiver).await.is_empty());
+
+        alerter.observe_outcome(&vault, &failure(), &links());
+        let bodies = received(&mut receiver).await;
+        assert_eq!(bodies.len(), 1);
+        let text = bodies[0]["text"].as_str().unwrap();
+        assert!(text.contains("repeated crank failures"), "{}", text);
+        assert!(text.contains(&vault.savings_vault.to_string()), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn failure_alerts_do_not_carry_the_rpc_url() {
+        let (alerter, mut receiver) = alerter(Duration::from_secs(3600), 1).await;
+        let err = reqwest::Client::new()
+            .get("https://127.0.0.1:1/?api-key=SECRET")
+            .send()
+            .await
+            .unwrap_err();
+        alerter.observe_outcome(&vault(), &CrankOutcome::rpc_failure(&err.into()), &links());
+
+        let bodies = received(&mut receiver).await;
+        assert_eq!(bodies.len(), 1);
+        let text = bodies[0]["text"].as_str().unwrap();
+        assert!(!text.contains("SECRET"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn success_resets_the_failure_count() {
+        let (alerter, mut receiver) = alerter(Duration::from_secs(3600), 2).await;
+        let vault = vault();
+        let success = CrankOutcome::Success {
+            signature: Default::default(),
+            slot: 1,
+        };
+        alerter.observe_outcome(&vault, &failure(), &links());
+        alerter.observe_outcome(&vault, &success, &links());
+        alerter.observe_outcome(&vault, &failure(), &links());
+
+        assert!(received(&mut receiver).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn already_accrued_resets_the_failure_count() {
+        let (alerter, mut receiver) = alerter(Duration::from_secs(3600), 2).await;
+        let vault = vault();
+        alerter.observe_outcome(&vault, &failure(), &links());
+        alerter.observe_outcome(&vault, &already_accrued(), &links());
+        alerter.observe_outcome(&vault, &failure(), &links());
+        assert!(received(&mut receiver).await.is_empty());
+
+        alerter.observe_outcome(&vault, &failure(), &links());
+        assert_eq!(received(&mut receiver).await.len(), 1);
+    }
+}
@@ -0,0 +1,99 @@
+use {
+    std::{
+        collections::VecDeque,
+        time::{Duration, Instant},
+    },
+};
+
+/// How far back balance samples are kept to estimate the cranker's fee spend.
+pub const FEE_SPEND_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Balances, in lamports, at which the cranker keypair is reported low or stops cranking.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BalancePolicy {
+    /// Below this balance a warning is logged every cycle.
+    pub warn_lamports: u64,
+    /// Below this balance no accrue transactions are sent until the keypair is topped up.
+    pub floor_lamports: u64,
+}
+
+impl BalancePolicy {
+    pub fn status(&self, lamports: u64) -> BalanceStatus {
+        if lamports < self.floor_lamports {
+            BalanceStatus::BelowFloor
+        } else if lamports < self.warn_lamports {
+            BalanceStatus::Low
+        } else {
+            BalanceStatus::Healthy
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BalanceStatus {
+    Healthy,
+    Low,
+    BelowFloor,
+}
+
+/// A single balance check of the cranker keypair.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BalanceReport {
+    pub lamports: u64,
+    pub status: BalanceStatus,
+    /// Time until the balance runs out at the fee spend observed over `FEE_SPEND_WINDOW`,
+    /// `None` before any spend has been observed.
+    pub runway: Option<Duration>,
+}
+
+/// Tracks the cranker's balance across cycles to classify it and estimate its runway.
+#[derive(Debug)]
+pub struct BalanceMonitor {
+    policy: BalancePolicy,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl BalanceMonitor {
+    pub fn new(policy: BalancePolicy) -> Self {
+        Self {
+            policy,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Records the current balance and reports its status and runway.
+    pub fn observe(&mut self, lamports: u64) -> BalanceReport {
+        let now = Instant::now();
+        self.samples.push_back((now, lamports));
+        while let Some((sampled_at, _)) = self.samples.front() {
+            if now.duration_since(*sampled_at) <= FEE_SPEND_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+
+        BalanceReport {
+            lamports,
+            status: self.policy.status(lamports),
+            runway: self.runway(lamports),
+        }
+    }
+
+    /// Only decreases count as spend, so top-ups between samples do not hide the fee rate.
+    fn runway(&self, lamports: u64) -> Option<Duration> {
+        let (first, _) = self.samples.front()?;
+        let (last, _) = self.samples.back()?;
+        let elapsed = last.duration_since(*first).as_secs_f64();
+        let spent: u64 = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .map(|((_, before), (_, after))| before.saturating_sub(*after))
+            .sum();
+        if spent == 0 || elapsed <= 0.0 {
+            return None;
+        }
+        let lamports_per_sec = spent as f64 / elapsed;
+        Some(Duration::from_secs_f64(lamports as f64 / lamports_per_sec))
+    }
+}
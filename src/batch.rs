@@ -0,0 +1,234 @@
+use {
+    solana_sdk::{
+        compute_budget::ComputeBudgetInstruction,
+        instruction::Instruction,
+        packet::PACKET_DATA_SIZE,
+        pubkey::Pubkey,
+        instruction::InstructionError,
+        transaction::{Transaction, TransactionError},
+    },
+    crate::{config::MAX_COMPUTE_UNITS, discovery::DiscoveredVault},
+};
+
+/// Compute unit limit and price instructions leading every accrue transaction.
+pub const COMPUTE_BUDGET_INSTRUCTIONS: usize = 2;
+
+/// `AccrueInterest` instructions for several savings vaults sent as one transaction.
+#[derive(Clone, Debug, Default)]
+pub struct AccrueBatch {
+    pub vaults: Vec<DiscoveredVault>,
+    pub instructions: Vec<Instruction>,
+}
+
+impl AccrueBatch {
+    pub fn len(&self) -> usize {
+        self.vaults.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vaults.is_empty()
+    }
+
+    pub fn push(&mut self, vault: DiscoveredVault, instruction: Instruction) {
+        self.vaults.push(vault);
+        self.instructions.push(instruction);
+    }
+
+    pub fn remove(&mut self, index: usize) -> (DiscoveredVault, Instruction) {
+        (self.vaults.remove(index), self.instructions.remove(index))
+    }
+
+    /// Splits the batch into two halves, the first one holding the extra vault of an odd batch.
+    pub fn split(mut self) -> (Self, Self) {
+        let at = (self.len() + 1) / 2;
+        let second = Self {
+            vaults: self.vaults.split_off(at),
+            instructions: self.instructions.split_off(at),
+        };
+        (self, second)
+    }
+
+    /// The shared compute budget instructions followed by every accrue instruction.
+    pub fn transaction_instructions(&self, compute_unit_limit: u32, compute_unit_price: u64) -> Vec<Instruction> {
+        let mut instructions = Vec::with_capacity(self.len() + COMPUTE_BUDGET_INSTRUCTIONS);
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(
+            compute_unit_limit.min(MAX_COMPUTE_UNITS),
+        ));
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price));
+        instructions.extend(self.instructions.iter().cloned());
+        instructions
+    }
+
+    /// Index into `vaults` of the accrue instruction at `instruction_index` of the transaction.
+    pub fn vault_index(&self, instruction_index: u8) -> Option<usize> {
+        (instruction_index as usize)
+            .checked_sub(COMPUTE_BUDGET_INSTRUCTIONS)
+            .filter(|index| *index < self.len())
+    }
+}
+
+/// Greedily packs accrue instructions into batches that stay within `max_batch_size` and the
+/// transaction size limit.
+///
+/// Compute units are not accounted for here: each batch is simulated before sending and split
+/// if it exceeds the compute unit limit.
+pub fn pack_batches(
+    payer: &Pubkey,
+    max_batch_size: usize,
+    accruals: Vec<(DiscoveredVault, Instruction)>,
+) -> Vec<AccrueBatch> {
+    let mut batches = Vec::new();
+    let mut current = AccrueBatch::default();
+    for (vault, instruction) in accruals {
+        current.push(vault, instruction);
+        if current.len() > 1 && !fits(payer, max_batch_size, &current) {
+            let (vault, instruction) = current.remove(current.len() - 1);
+            batches.push(std::mem::take(&mut current));
+            current.push(vault, instruction);
+        }
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+fn fits(payer: &Pubkey, max_batch_size: usize, batch: &AccrueBatch) -> bool {
+    if batch.len() > max_batch_size {
+        return false;
+    }
+    // An unsigned transaction carries zeroed placeholder signatures, so its size is exact.
+    let transaction = Transaction::new_with_payer(
+        &batch.transaction_instructions(MAX_COMPUTE_UNITS, 0),
+        Some(payer),
+    );
+    bincode::serialized_size(&transaction).map_or(false, |size| size as usize <= PACKET_DATA_SIZE)
+}
+
+/// Index of the instruction that made `error` fail, if the failure is tied to one.
+///
+/// Running out of compute units is blamed on the batch rather than the instruction that
+/// happened to be executing.
+pub fn failed_instruction_index(error: &TransactionError) -> Option<u8> {
+    match error {
+        TransactionError::InstructionError(_, InstructionError::ComputationalBudgetExceeded) => None,
+        TransactionError::InstructionError(index, _) => Some(*index),
+        _ => None,
+    }
+}
+
+/// Compute unit limit covering `units_consumed` in simulation plus a `margin` fraction.
+pub fn compute_unit_limit(units_consumed: u64, margin: f64) -> u32 {
+    let limit = (units_consumed as f64 * (1.0 + margin)).ceil() as u64;
+    limit.clamp(1, u64::from(MAX_COMPUTE_UNITS)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::instruction::AccountMeta,
+        crate::vault_state::VaultSchedule,
+    };
+
+    fn vault() -> DiscoveredVault {
+        DiscoveredVault {
+            savings_vault: Pubkey::new_unique(),
+            wallet: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            schedule: VaultSchedule {
+                last_accrued_at: 0,
+                interest_period: 0,
+            },
+            interest_rate_bps: 100,
+        }
+    }
+
+    /// An accrue-sized instruction writing `accounts` distinct accounts.
+    fn accrual(program_id: &Pubkey, accounts: usize) -> (DiscoveredVault, Instruction) {
+        let metas = (0..accounts)
+            .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+            .collect();
+        (vault(), Instruction::new_with_bytes(*program_id, &[0; 8], metas))
+    }
+
+    fn batch(len: usize) -> AccrueBatch {
+        let program_id = Pubkey::new_unique();
+        let mut batch = AccrueBatch::default();
+        for _ in 0..len {
+            let (vault, instruction) = accrual(&program_id, 0);
+            batch.push(vault, instruction);
+        }
+        batch
+    }
+
+    #[test]
+    fn packs_up_to_the_packet_size() {
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let accruals: Vec<_> = (0..20).map(|_| accrual(&program_id, 7)).collect();
+        let vaults: Vec<Pubkey> = accruals.iter().map(|(vault, _)| vault.savings_vault).collect();
+
+        let batches = pack_batches(&payer, 100, accruals);
+        assert!(batches.len() > 1);
+        for (batch, next) in batches.iter().zip(batches.iter().skip(1)) {
+            assert!(fits(&payer, 100, batch));
+            let mut grown = batch.clone();
+            grown.push(next.vaults[0], next.instructions[0].clone());
+            assert!(!fits(&payer, 100, &grown));
+        }
+        let packed: Vec<Pubkey> = batches
+            .iter()
+            .flat_map(|batch| batch.vaults.iter().map(|vault| vault.savings_vault))
+            .collect();
+        assert_eq!(packed, vaults);
+    }
+
+    #[test]
+    fn packs_up_to_the_max_batch_size() {
+        let program_id = Pubkey::new_unique();
+        let accruals = (0..7).map(|_| accrual(&program_id, 0)).collect();
+        let batches = pack_batches(&Pubkey::new_unique(), 3, accruals);
+        let lens: Vec<usize> = batches.iter().map(AccrueBatch::len).collect();
+        assert_eq!(lens, vec![3, 3, 1]);
+    }
+
+    #[test]
+    fn split_gives_the_extra_vault_to_the_first_half() {
+        let batch = batch(5);
+        let vaults = batch.vaults.clone();
+        let (first, second) = batch.split();
+        assert_eq!(first.len(), 3);
+        assert_eq!(second.len(), 2);
+        assert_eq!(first.vaults, vaults[..3]);
+        assert_eq!(second.vaults, vaults[3..]);
+        assert_eq!(first.instructions.len(), 3);
+        assert_eq!(second.instructions.len(), 2);
+    }
+
+    #[test]
+    fn vault_index_skips_the_compute_budget_instructions() {
+        let batch = batch(2);
+        assert_eq!(batch.vault_index(0), None);
+        assert_eq!(batch.vault_index(1), None);
+        assert_eq!(batch.vault_index(2), Some(0));
+        assert_eq!(batch.vault_index(3), Some(1));
+        assert_eq!(batch.vault_index(4), None);
+    }
+
+    #[test]
+    fn blames_instructions_but_not_compute_exhaustion() {
+        assert_eq!(
+            failed_instruction_index(&TransactionError::InstructionError(3, InstructionError::Custom(6000))),
+            Some(3)
+        );
+        assert_eq!(
+            failed_instruction_index(&TransactionError::InstructionError(
+                3,
+                InstructionError::ComputationalBudgetExceeded
+            )),
+            None
+        );
+        assert_eq!(failed_instruction_index(&TransactionError::BlockhashNotFound), None);
+    }
+}
@@ -0,0 +1,833 @@
+use {
+    std::{
+        collections::HashMap,
+        env,
+        fmt,
+        fs,
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        str::FromStr,
+        time::Duration,
+    },
+    clap::{Parser, Subcommand},
+    serde::Deserialize,
+    solana_sdk::{
+        commitment_config::{CommitmentConfig, CommitmentLevel},
+        hash::Hash,
+        native_token::sol_to_lamports,
+        pubkey::Pubkey,
+    },
+    anyhow::{Context, Result},
+    crate::{
+        alert::WebhookFormat,
+        balance::BalancePolicy,
+        client::{header_map, ClientConfig},
+        explorer::Explorer,
+        logging::LogFormat,
+        priority_fee::PriorityFeePolicy,
+        retry::RetryPolicy,
+        schedule::{CrankSchedule, IsoInterval},
+        SAVINGS_VAULT_PROGRAM_ID,
+    },
+};
+
+pub const DEFAULT_CONFIG_PATH: &str = "crank.toml";
+pub const DEFAULT_RPC_URL: &str = "https://api.devnet.solana.com";
+pub const DEFAULT_COMPUTE_UNITS: u32 = 400_000;
+pub const DEFAULT_STATE_PATH: &str = "crank_state.db";
+pub const DEFAULT_ACCRUAL_INTERVAL_DAYS: i64 = 30;
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 60 * 60;
+pub const DEFAULT_COMMITMENT: &str = "confirmed";
+pub const DEFAULT_RPC_MAX_SLOT_LAG: u64 = 50;
+pub const DEFAULT_RPC_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_RPC_HEALTH_CHECK_SECS: u64 = 30;
+pub const DEFAULT_LOG_FORMAT: &str = "text";
+pub const DEFAULT_EXPLORER: &str = "solana";
+pub const DEFAULT_CONFIRM_TIMEOUT_SECS: u64 = 60;
+pub const DEFAULT_RETRY_MAX_ATTEMPTS: u32 = 4;
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 500;
+pub const DEFAULT_RETRY_MAX_DELAY_MS: u64 = 15_000;
+pub const DEFAULT_RETRY_JITTER: f64 = 0.2;
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 8;
+pub const DEFAULT_CONCURRENCY: usize = 4;
+pub const DEFAULT_BALANCE_WARN_SOL: f64 = 1.0;
+pub const DEFAULT_BALANCE_FLOOR_SOL: f64 = 0.05;
+pub const DEFAULT_ALERT_WEBHOOK_FORMAT: &str = "slack";
+pub const DEFAULT_ALERT_DEDUP_WINDOW_SECS: u64 = 60 * 60;
+pub const DEFAULT_ALERT_FAILURE_THRESHOLD: u32 = 3;
+pub const DEFAULT_COMPUTE_UNIT_MARGIN: f64 = 0.1;
+pub const DEFAULT_PRIORITY_FEE_PERCENTILE: u8 = 75;
+pub const DEFAULT_PRIORITY_FEE_MIN_MICRO_LAMPORTS: u64 = 0;
+pub const DEFAULT_PRIORITY_FEE_MAX_MICRO_LAMPORTS: u64 = 100_000;
+pub const DEFAULT_PRIORITY_FEE_ESCALATION: f64 = 1.5;
+
+/// Upper bound the runtime allows for a single transaction.
+pub const MAX_COMPUTE_UNITS: u32 = 1_400_000;
+
+/// Command line flags. Every flag can also be set through its `CRANK_*` environment variable,
+/// and both take precedence over the config file.
+#[derive(Debug, Default, Parser)]
+#[command(version, about = "Cranks AccrueInterest for every savings vault")]
+pub struct Cli {
+    /// Path to the TOML config file (defaults to ./crank.toml when present)
+    #[arg(long, short, env = "CRANK_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Keypair that signs and pays for accrue transactions
+    #[arg(long, env = "CRANK_KEYPAIR_PATH")]
+    pub keypair_path: Option<PathBuf>,
+
+    /// JSON RPC endpoint of the cluster to crank
+    #[arg(long, env = "CRANK_RPC_URL")]
+    pub rpc_url: Option<String>,
+
+    /// Further JSON RPC endpoints, comma separated, failed over to when rpc_url is unhealthy
+    #[arg(long, env = "CRANK_RPC_FAILOVER_URLS", value_delimiter = ',')]
+    pub rpc_failover_urls: Option<Vec<String>>,
+
+    /// Websocket endpoint of rpc_url used to confirm sent transactions, derived from rpc_url when unset
+    #[arg(long, env = "CRANK_RPC_WS_URL")]
+    pub rpc_ws_url: Option<String>,
+
+    /// Header sent with every RPC request as "Name: value", e.g. a provider API key; repeatable
+    #[arg(long = "rpc-header", env = "CRANK_RPC_HEADERS", value_delimiter = ',')]
+    pub rpc_headers: Option<Vec<String>>,
+
+    /// Seconds before a single RPC request times out
+    #[arg(long, env = "CRANK_RPC_TIMEOUT_SECS")]
+    pub rpc_timeout_secs: Option<u64>,
+
+    /// Slots an endpoint may fall behind the most advanced one before it is failed over from
+    #[arg(long, env = "CRANK_RPC_MAX_SLOT_LAG")]
+    pub rpc_max_slot_lag: Option<u64>,
+
+    /// Seconds between RPC endpoint health checks
+    #[arg(long, env = "CRANK_RPC_HEALTH_CHECK_SECS")]
+    pub rpc_health_check_secs: Option<u64>,
+
+    /// Cluster the RPC endpoints must serve: mainnet, devnet, testnet, localnet or a genesis hash;
+    /// not checked when unset
+    #[arg(long, env = "CRANK_CLUSTER")]
+    pub cluster: Option<String>,
+
+    /// Savings vault program id
+    #[arg(long, env = "CRANK_PROGRAM_ID")]
+    pub program_id: Option<String>,
+
+    /// Compute units budgeted per accrue instruction when simulation is unavailable
+    #[arg(long, env = "CRANK_COMPUTE_UNITS")]
+    pub compute_units: Option<u32>,
+
+    /// Fraction added on top of the simulated compute units, e.g. 0.1 for 10%
+    #[arg(long, env = "CRANK_COMPUTE_UNIT_MARGIN")]
+    pub compute_unit_margin: Option<f64>,
+
+    /// Path of the SQLite crank state database
+    #[arg(long, env = "CRANK_STATE_PATH")]
+    pub state_path: Option<PathBuf>,
+
+    /// Days between two accruals for savings vaults whose account sets no interest period
+    #[arg(long, env = "CRANK_ACCRUAL_INTERVAL_DAYS")]
+    pub accrual_interval_days: Option<i64>,
+
+    /// Seconds between two rescans of the savings vaults and their schedules
+    #[arg(long, env = "CRANK_POLL_INTERVAL_SECS")]
+    pub poll_interval_secs: Option<u64>,
+
+    /// Commitment accrue transactions must reach: processed, confirmed or finalized
+    #[arg(long, env = "CRANK_COMMITMENT")]
+    pub commitment: Option<String>,
+
+    /// Seconds to wait for an accrue transaction to reach the commitment
+    #[arg(long, env = "CRANK_CONFIRM_TIMEOUT_SECS")]
+    pub confirm_timeout_secs: Option<u64>,
+
+    /// Attempts per crank, including the first, before giving up on a retryable failure
+    #[arg(long, env = "CRANK_RETRY_MAX_ATTEMPTS")]
+    pub retry_max_attempts: Option<u32>,
+
+    /// Delay before the first retry, doubled on every further attempt
+    #[arg(long, env = "CRANK_RETRY_BASE_DELAY_MS")]
+    pub retry_base_delay_ms: Option<u64>,
+
+    /// Upper bound on the delay between two attempts
+    #[arg(long, env = "CRANK_RETRY_MAX_DELAY_MS")]
+    pub retry_max_delay_ms: Option<u64>,
+
+    /// Random fraction (0.0 to 1.0) applied to every retry delay
+    #[arg(long, env = "CRANK_RETRY_JITTER")]
+    pub retry_jitter: Option<f64>,
+
+    /// Most savings vaults accrued in a single transaction, 1 disables batching
+    #[arg(long, env = "CRANK_MAX_BATCH_SIZE")]
+    pub max_batch_size: Option<usize>,
+
+    /// Percentile (0 to 100) of recent prioritization fees to bid
+    #[arg(long, env = "CRANK_PRIORITY_FEE_PERCENTILE")]
+    pub priority_fee_percentile: Option<u8>,
+
+    /// Lowest compute unit price, in micro-lamports
+    #[arg(long, env = "CRANK_PRIORITY_FEE_MIN_MICRO_LAMPORTS")]
+    pub priority_fee_min_micro_lamports: Option<u64>,
+
+    /// Highest compute unit price, in micro-lamports, retries included
+    #[arg(long, env = "CRANK_PRIORITY_FEE_MAX_MICRO_LAMPORTS")]
+    pub priority_fee_max_micro_lamports: Option<u64>,
+
+    /// Multiplier applied to the compute unit price on every retry
+    #[arg(long, env = "CRANK_PRIORITY_FEE_ESCALATION")]
+    pub priority_fee_escalation: Option<f64>,
+
+    /// Most accrue transactions in flight at once
+    #[arg(long, env = "CRANK_CONCURRENCY")]
+    pub concurrency: Option<usize>,
+
+    /// Cranker SOL balance below which a warning is logged every cycle
+    #[arg(long, env = "CRANK_BALANCE_WARN_SOL")]
+    pub balance_warn_sol: Option<f64>,
+
+    /// Cranker SOL balance below which cranking pauses until the keypair is topped up
+    #[arg(long, env = "CRANK_BALANCE_FLOOR_SOL")]
+    pub balance_floor_sol: Option<f64>,
+
+    /// Webhook alerts are posted to; no alerts are sent when unset
+    #[arg(long, env = "CRANK_ALERT_WEBHOOK_URL")]
+    pub alert_webhook_url: Option<String>,
+
+    /// JSON payload posted to the alert webhook, slack or discord
+    #[arg(long, env = "CRANK_ALERT_WEBHOOK_FORMAT")]
+    pub alert_webhook_format: Option<String>,
+
+    /// Seconds during which repeats of the same alert are dropped
+    #[arg(long, env = "CRANK_ALERT_DEDUP_WINDOW_SECS")]
+    pub alert_dedup_window_secs: Option<u64>,
+
+    /// Consecutive failed cranks of a vault before alerting
+    #[arg(long, env = "CRANK_ALERT_FAILURE_THRESHOLD")]
+    pub alert_failure_threshold: Option<u32>,
+
+    /// Address to serve Prometheus metrics on, e.g. 0.0.0.0:9100; metrics are not served when unset
+    #[arg(long, env = "CRANK_METRICS_ADDR")]
+    pub metrics_addr: Option<String>,
+
+    /// Explorer linked from logs and alerts: solana, solscan or solanafm
+    #[arg(long, env = "CRANK_EXPLORER")]
+    pub explorer: Option<String>,
+
+    /// Log output, text or json
+    #[arg(long, env = "CRANK_LOG_FORMAT")]
+    pub log_format: Option<String>,
+
+    /// Simulate one pass over the due savings vaults and print the result without sending
+    #[arg(long, env = "CRANK_DRY_RUN")]
+    pub dry_run: bool,
+
+    /// What to do, `run` when omitted
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Accrue interest on a single savings vault now
+    Crank {
+        #[arg(long, value_parser = Pubkey::from_str)]
+        wallet: Pubkey,
+        #[arg(long, value_parser = Pubkey::from_str)]
+        mint: Pubkey,
+    },
+    /// List the discovered savings vaults with their PDAs and next due time
+    List,
+    /// Show the cranker balance and the interest depositor treasury of every mint
+    Status,
+    /// Print the four PDAs of a wallet and mint
+    Derive {
+        #[arg(long, value_parser = Pubkey::from_str)]
+        wallet: Pubkey,
+        #[arg(long, value_parser = Pubkey::from_str)]
+        mint: Pubkey,
+    },
+    /// Crank every savings vault as it comes due until stopped
+    Run,
+}
+
+/// Contents of the TOML config file. Every key is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub keypair_path: Option<PathBuf>,
+    pub rpc_url: Option<String>,
+    pub rpc_failover_urls: Option<Vec<String>>,
+    pub rpc_ws_url: Option<String>,
+    /// Headers sent with every RPC request, only configurable as a table in the file.
+    pub rpc_headers: Option<HashMap<String, String>>,
+    pub rpc_timeout_secs: Option<u64>,
+    pub rpc_max_slot_lag: Option<u64>,
+    pub rpc_health_check_secs: Option<u64>,
+    pub cluster: Option<String>,
+    pub program_id: Option<String>,
+    pub compute_units: Option<u32>,
+    pub compute_unit_margin: Option<f64>,
+    pub state_path: Option<PathBuf>,
+    pub accrual_interval_days: Option<i64>,
+    pub poll_interval_secs: Option<u64>,
+    pub commitment: Option<String>,
+    pub confirm_timeout_secs: Option<u64>,
+    pub retry_max_attempts: Option<u32>,
+    pub retry_base_delay_ms: Option<u64>,
+    pub retry_max_delay_ms: Option<u64>,
+    pub retry_jitter: Option<f64>,
+    pub max_batch_size: Option<usize>,
+    pub priority_fee_percentile: Option<u8>,
+    pub priority_fee_min_micro_lamports: Option<u64>,
+    pub priority_fee_max_micro_lamports: Option<u64>,
+    pub priority_fee_escalation: Option<f64>,
+    pub concurrency: Option<usize>,
+    pub balance_warn_sol: Option<f64>,
+    pub balance_floor_sol: Option<f64>,
+    pub alert_webhook_url: Option<String>,
+    pub alert_webhook_format: Option<String>,
+    pub alert_dedup_window_secs: Option<u64>,
+    pub alert_failure_threshold: Option<u32>,
+    pub metrics_addr: Option<String>,
+    pub log_format: Option<String>,
+    pub explorer: Option<String>,
+    /// Per-mint crank cadence, only configurable in the file.
+    pub schedules: Vec<ScheduleEntry>,
+}
+
+/// A `[[schedules]]` table: the vaults of `mint` are cranked on `cron` or every `interval`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleEntry {
+    pub mint: String,
+    pub cron: Option<String>,
+    pub interval: Option<String>,
+}
+
+impl FileConfig {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+}
+
+/// A config value that failed validation, named by its config file key.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    pub(crate) fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid config `{}`: {}", self.key, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Fully resolved crank configuration.
+#[derive(Clone, Debug)]
+pub struct CrankConfig {
+    pub keypair_path: PathBuf,
+    pub rpc_url: String,
+    pub rpc_failover_urls: Vec<String>,
+    pub rpc_ws_url: Option<String>,
+    pub rpc_headers: HashMap<String, String>,
+    pub rpc_timeout: Duration,
+    pub rpc_max_slot_lag: u64,
+    pub rpc_health_check_interval: Duration,
+    /// Cluster the RPC endpoints must serve, see `DetectedCluster::matches`.
+    pub cluster: Option<String>,
+    pub program_id: Pubkey,
+    pub compute_units: u32,
+    pub compute_unit_margin: f64,
+    pub state_path: PathBuf,
+    pub accrual_interval_days: i64,
+    pub poll_interval: Duration,
+    pub commitment: CommitmentConfig,
+    pub confirm_timeout: Duration,
+    pub retry: RetryPolicy,
+    pub max_batch_size: usize,
+    pub priority_fee: PriorityFeePolicy,
+    pub concurrency: usize,
+    pub balance: BalancePolicy,
+    pub alert_webhook_url: Option<String>,
+    pub alert_webhook_format: WebhookFormat,
+    pub alert_dedup_window: Duration,
+    pub alert_failure_threshold: u32,
+    pub schedules: HashMap<Pubkey, CrankSchedule>,
+    pub metrics_addr: Option<SocketAddr>,
+    pub log_format: LogFormat,
+    pub explorer: Explorer,
+    pub dry_run: bool,
+}
+
+impl CrankConfig {
+    /// Resolves the configuration from, in order of precedence, CLI flags, `CRANK_*`
+    /// environment variables, the config file and built-in defaults.
+    pub fn load(cli: Cli) -> Result<Self> {
+        let file = match &cli.config {
+            Some(path) => FileConfig::from_path(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                FileConfig::from_path(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => FileConfig::default(),
+        };
+
+        let keypair_path = match cli.keypair_path.or(file.keypair_path) {
+            Some(path) => expand_tilde(path),
+            None => default_keypair_path()
+                .ok_or_else(|| ConfigError::new("keypair_path", "not set and $HOME is unknown"))?,
+        };
+        let rpc_url = cli.rpc_url.or(file.rpc_url).unwrap_or_else(|| DEFAULT_RPC_URL.to_string());
+        if !(rpc_url.starts_with("http://") || rpc_url.starts_with("https://")) {
+            return Err(ConfigError::new(
+                "rpc_url",
+                format!("{} must be an http:// or https:// URL", rpc_url),
+            )
+            .into());
+        }
+        let rpc_failover_urls = cli.rpc_failover_urls.or(file.rpc_failover_urls).unwrap_or_default();
+        for (index, url) in rpc_failover_urls.iter().enumerate() {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(ConfigError::new(
+                    format!("rpc_failover_urls[{}]", index),
+                    format!("{} must be an http:// or https:// URL", url),
+                )
+                .into());
+            }
+        }
+        let rpc_ws_url = cli.rpc_ws_url.or(file.rpc_ws_url);
+        if let Some(url) = &rpc_ws_url {
+            if !(url.starts_with("ws://") || url.starts_with("wss://")) {
+                return Err(
+                    ConfigError::new("rpc_ws_url", format!("{} must be a ws:// or wss:// URL", url)).into(),
+                );
+            }
+        }
+        let rpc_headers = match cli.rpc_headers {
+            Some(headers) => {
+                let mut parsed = HashMap::with_capacity(headers.len());
+                for header in headers {
+                    let (name, value) = header.split_once(':').ok_or_else(|| {
+                        ConfigError::new("rpc_headers", format!("{} must have the form \"Name: value\"", header))
+                    })?;
+                    parsed.insert(name.trim().to_string(), value.trim().to_string());
+                }
+                parsed
+            }
+            None => file.rpc_headers.unwrap_or_default(),
+        };
+        header_map(&rpc_headers).map_err(|err| ConfigError::new("rpc_headers", err.to_string()))?;
+        let rpc_timeout_secs = cli
+            .rpc_timeout_secs
+            .or(file.rpc_timeout_secs)
+            .unwrap_or(DEFAULT_RPC_TIMEOUT_SECS);
+        if rpc_timeout_secs == 0 {
+            return Err(ConfigError::new("rpc_timeout_secs", "must be greater than zero").into());
+        }
+        let rpc_max_slot_lag = cli
+            .rpc_max_slot_lag
+            .or(file.rpc_max_slot_lag)
+            .unwrap_or(DEFAULT_RPC_MAX_SLOT_LAG);
+        let rpc_health_check_secs = cli
+            .rpc_health_check_secs
+            .or(file.rpc_health_check_secs)
+            .unwrap_or(DEFAULT_RPC_HEALTH_CHECK_SECS);
+        if rpc_health_check_secs == 0 {
+            return Err(ConfigError::new("rpc_health_check_secs", "must be greater than zero").into());
+        }
+
+        let cluster = cli.cluster.or(file.cluster);
+        if let Some(cluster) = &cluster {
+            let known = ["mainnet", "mainnet-beta", "devnet", "testnet", "localnet"].contains(&cluster.as_str());
+            if !known && Hash::from_str(cluster).is_err() {
+                return Err(ConfigError::new(
+                    "cluster",
+                    format!(
+                        "{} must be one of mainnet, devnet, testnet, localnet or a genesis hash",
+                        cluster
+                    ),
+                )
+                .into());
+            }
+        }
+
+        let program_id = cli
+            .program_id
+            .or(file.program_id)
+            .unwrap_or_else(|| SAVINGS_VAULT_PROGRAM_ID.to_string());
+        let program_id = Pubkey::from_str(&program_id).map_err(|err| {
+            ConfigError::new("program_id", format!("{} is not a valid pubkey: {}", program_id, err))
+        })?;
+
+        let compute_units = cli.compute_units.or(file.compute_units).unwrap_or(DEFAULT_COMPUTE_UNITS);
+        if compute_units == 0 || compute_units > MAX_COMPUTE_UNITS {
+            return Err(ConfigError::new(
+                "compute_units",
+                format!("{} must be between 1 and {}", compute_units, MAX_COMPUTE_UNITS),
+            )
+            .into());
+        }
+
+        let compute_unit_margin = cli
+            .compute_unit_margin
+            .or(file.compute_unit_margin)
+            .unwrap_or(DEFAULT_COMPUTE_UNIT_MARGIN);
+        if !(0.0..=1.0).contains(&compute_unit_margin) {
+            return Err(ConfigError::new(
+                "compute_unit_margin",
+                format!("{} must be between 0.0 and 1.0", compute_unit_margin),
+            )
+            .into());
+        }
+
+        let state_path = cli
+            .state_path
+            .or(file.state_path)
+            .map(expand_tilde)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_PATH));
+
+        let accrual_interval_days = cli
+            .accrual_interval_days
+            .or(file.accrual_interval_days)
+            .unwrap_or(DEFAULT_ACCRUAL_INTERVAL_DAYS);
+        if accrual_interval_days <= 0 {
+            return Err(ConfigError::new("accrual_interval_days", "must be greater than zero").into());
+        }
+
+        let poll_interval_secs = cli
+            .poll_interval_secs
+            .or(file.poll_interval_secs)
+            .unwrap_or(DEFAULT_POLL_INTERVAL_SECS);
+        if poll_interval_secs == 0 {
+            return Err(ConfigError::new("poll_interval_secs", "must be greater than zero").into());
+        }
+
+        let commitment = cli
+            .commitment
+            .or(file.commitment)
+            .unwrap_or_else(|| DEFAULT_COMMITMENT.to_string());
+        let commitment = match commitment.as_str() {
+            "processed" => CommitmentLevel::Processed,
+            "confirmed" => CommitmentLevel::Confirmed,
+            "finalized" => CommitmentLevel::Finalized,
+            _ => {
+                return Err(ConfigError::new(
+                    "commitment",
+                    format!("{} must be one of processed, confirmed or finalized", commitment),
+                )
+                .into())
+            }
+        };
+
+        let confirm_timeout_secs = cli
+            .confirm_timeout_secs
+            .or(file.confirm_timeout_secs)
+            .unwrap_or(DEFAULT_CONFIRM_TIMEOUT_SECS);
+        if confirm_timeout_secs == 0 {
+            return Err(ConfigError::new("confirm_timeout_secs", "must be greater than zero").into());
+        }
+
+        let retry_max_attempts = cli
+            .retry_max_attempts
+            .or(file.retry_max_attempts)
+            .unwrap_or(DEFAULT_RETRY_MAX_ATTEMPTS);
+        if retry_max_attempts == 0 {
+            return Err(ConfigError::new("retry_max_attempts", "must be at least 1").into());
+        }
+        let retry_base_delay_ms = cli
+            .retry_base_delay_ms
+            .or(file.retry_base_delay_ms)
+            .unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS);
+        let retry_max_delay_ms = cli
+            .retry_max_delay_ms
+            .or(file.retry_max_delay_ms)
+            .unwrap_or(DEFAULT_RETRY_MAX_DELAY_MS);
+        if retry_max_delay_ms < retry_base_delay_ms {
+            return Err(ConfigError::new(
+                "retry_max_delay_ms",
+                format!("{} must not be less than retry_base_delay_ms ({})", retry_max_delay_ms, retry_base_delay_ms),
+            )
+            .into());
+        }
+        let retry_jitter = cli.retry_jitter.or(file.retry_jitter).unwrap_or(DEFAULT_RETRY_JITTER);
+        if !(0.0..=1.0).contains(&retry_jitter) {
+            return Err(ConfigError::new(
+                "retry_jitter",
+                format!("{} must be between 0.0 and 1.0", retry_jitter),
+            )
+            .into());
+        }
+
+        let max_batch_size = cli.max_batch_size.or(file.max_batch_size).unwrap_or(DEFAULT_MAX_BATCH_SIZE);
+        if max_batch_size == 0 {
+            return Err(ConfigError::new("max_batch_size", "must be at least 1").into());
+        }
+
+        let priority_fee_percentile = cli
+            .priority_fee_percentile
+            .or(file.priority_fee_percentile)
+            .unwrap_or(DEFAULT_PRIORITY_FEE_PERCENTILE);
+        if priority_fee_percentile > 100 {
+            return Err(ConfigError::new(
+                "priority_fee_percentile",
+                format!("{} must be between 0 and 100", priority_fee_percentile),
+            )
+            .into());
+        }
+        let priority_fee_min_micro_lamports = cli
+            .priority_fee_min_micro_lamports
+            .or(file.priority_fee_min_micro_lamports)
+            .unwrap_or(DEFAULT_PRIORITY_FEE_MIN_MICRO_LAMPORTS);
+        let priority_fee_max_micro_lamports = cli
+            .priority_fee_max_micro_lamports
+            .or(file.priority_fee_max_micro_lamports)
+            .unwrap_or(DEFAULT_PRIORITY_FEE_MAX_MICRO_LAMPORTS);
+        if priority_fee_max_micro_lamports < priority_fee_min_micro_lamports {
+            return Err(ConfigError::new(
+                "priority_fee_max_micro_lamports",
+                format!(
+                    "{} must not be less than priority_fee_min_micro_lamports ({})",
+                    priority_fee_max_micro_lamports, priority_fee_min_micro_lamports
+                ),
+            )
+            .into());
+        }
+        let priority_fee_escalation = cli
+            .priority_fee_escalation
+            .or(file.priority_fee_escalation)
+            .unwrap_or(DEFAULT_PRIORITY_FEE_ESCALATION);
+        if priority_fee_escalation < 1.0 {
+            return Err(ConfigError::new(
+                "priority_fee_escalation",
+                format!("{} must be at least 1.0", priority_fee_escalation),
+            )
+            .into());
+        }
+
+        let concurrency = cli.concurrency.or(file.concurrency).unwrap_or(DEFAULT_CONCURRENCY);
+        if concurrency == 0 {
+            return Err(ConfigError::new("concurrency", "must be at least 1").into());
+        }
+
+        let balance_warn_sol = cli
+            .balance_warn_sol
+            .or(file.balance_warn_sol)
+            .unwrap_or(DEFAULT_BALANCE_WARN_SOL);
+        if !(0.0..=f64::MAX).contains(&balance_warn_sol) {
+            return Err(ConfigError::new("balance_warn_sol", format!("{} must not be negative", balance_warn_sol)).into());
+        }
+        let balance_floor_sol = cli
+            .balance_floor_sol
+            .or(file.balance_floor_sol)
+            .unwrap_or(DEFAULT_BALANCE_FLOOR_SOL);
+        if !(0.0..=balance_warn_sol).contains(&balance_floor_sol) {
+            return Err(ConfigError::new(
+                "balance_floor_sol",
+                format!(
+                    "{} must be between 0 and balance_warn_sol ({})",
+                    balance_floor_sol, balance_warn_sol
+                ),
+            )
+            .into());
+        }
+
+        let alert_webhook_url = cli.alert_webhook_url.or(file.alert_webhook_url);
+        if let Some(url) = &alert_webhook_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(ConfigError::new("alert_webhook_url", format!("{} must be an http:// or https:// URL", url)).into());
+            }
+        }
+        let alert_webhook_format = cli
+            .alert_webhook_format
+            .or(file.alert_webhook_format)
+            .unwrap_or_else(|| DEFAULT_ALERT_WEBHOOK_FORMAT.to_string());
+        let alert_webhook_format = match alert_webhook_format.as_str() {
+            "slack" => WebhookFormat::Slack,
+            "discord" => WebhookFormat::Discord,
+            _ => {
+                return Err(ConfigError::new(
+                    "alert_webhook_format",
+                    format!("{} must be one of slack or discord", alert_webhook_format),
+                )
+                .into())
+            }
+        };
+        let alert_dedup_window_secs = cli
+            .alert_dedup_window_secs
+            .or(file.alert_dedup_window_secs)
+            .unwrap_or(DEFAULT_ALERT_DEDUP_WINDOW_SECS);
+        let alert_failure_threshold = cli
+            .alert_failure_threshold
+            .or(file.alert_failure_threshold)
+            .unwrap_or(DEFAULT_ALERT_FAILURE_THRESHOLD);
+        if alert_failure_threshold == 0 {
+            return Err(ConfigError::new("alert_failure_threshold", "must be at least 1").into());
+        }
+
+        let metrics_addr = match cli.metrics_addr.or(file.metrics_addr) {
+            Some(addr) => Some(SocketAddr::from_str(&addr).map_err(|err| {
+                ConfigError::new("metrics_addr", format!("{} is not a valid socket address: {}", addr, err))
+            })?),
+            None => None,
+        };
+
+        let log_format = cli
+            .log_format
+            .or(file.log_format)
+            .unwrap_or_else(|| DEFAULT_LOG_FORMAT.to_string());
+        let log_format = match log_format.as_str() {
+            "text" => LogFormat::Text,
+            "json" => LogFormat::Json,
+            _ => {
+                return Err(
+                    ConfigError::new("log_format", format!("{} must be one of text or json", log_format)).into(),
+                )
+            }
+        };
+
+        let explorer = cli.explorer.or(file.explorer).unwrap_or_else(|| DEFAULT_EXPLORER.to_string());
+        let explorer = match explorer.as_str() {
+            "solana" => Explorer::Solana,
+            "solscan" => Explorer::Solscan,
+            "solanafm" => Explorer::SolanaFm,
+            _ => {
+                return Err(ConfigError::new(
+                    "explorer",
+                    format!("{} must be one of solana, solscan or solanafm", explorer),
+                )
+                .into())
+            }
+        };
+
+        let mut schedules = HashMap::with_capacity(file.schedules.len());
+        for (index, entry) in file.schedules.into_iter().enumerate() {
+            let mint = Pubkey::from_str(&entry.mint).map_err(|err| {
+                ConfigError::new(
+                    format!("schedules[{}].mint", index),
+                    format!("{} is not a valid pubkey: {}", entry.mint, err),
+                )
+            })?;
+            let schedule = match (entry.cron, entry.interval) {
+                (Some(expression), None) => CrankSchedule::Cron(cron::Schedule::from_str(&expression).map_err(|err| {
+                    ConfigError::new(
+                        format!("schedules[{}].cron", index),
+                        format!("{} is not a valid cron expression: {}", expression, err),
+                    )
+                })?),
+                (None, Some(interval)) => CrankSchedule::Interval(
+                    IsoInterval::from_str(&interval)
+                        .map_err(|err| ConfigError::new(format!("schedules[{}].interval", index), err))?,
+                ),
+                _ => {
+                    return Err(ConfigError::new(
+                        format!("schedules[{}]", index),
+                        "must set exactly one of cron or interval",
+                    )
+                    .into())
+                }
+            };
+            if schedules.insert(mint, schedule).is_some() {
+                return Err(ConfigError::new(
+                    format!("schedules[{}].mint", index),
+                    format!("{} already has a schedule", mint),
+                )
+                .into());
+            }
+        }
+
+        Ok(Self {
+            keypair_path,
+            rpc_url,
+            rpc_failover_urls,
+            rpc_ws_url,
+            rpc_headers,
+            rpc_timeout: Duration::from_secs(rpc_timeout_secs),
+            rpc_max_slot_lag,
+            rpc_health_check_interval: Duration::from_secs(rpc_health_check_secs),
+            cluster,
+            program_id,
+            compute_units,
+            compute_unit_margin,
+            state_path,
+            accrual_interval_days,
+            poll_interval: Duration::from_secs(poll_interval_secs),
+            commitment: CommitmentConfig { commitment },
+            confirm_timeout: Duration::from_secs(confirm_timeout_secs),
+            retry: RetryPolicy {
+                max_attempts: retry_max_attempts,
+                base_delay: Duration::from_millis(retry_base_delay_ms),
+                max_delay: Duration::from_millis(retry_max_delay_ms),
+                jitter: retry_jitter,
+            },
+            max_batch_size,
+            priority_fee: PriorityFeePolicy {
+                percentile: priority_fee_percentile,
+                min_micro_lamports: priority_fee_min_micro_lamports,
+                max_micro_lamports: priority_fee_max_micro_lamports,
+                escalation: priority_fee_escalation,
+            },
+            concurrency,
+            balance: BalancePolicy {
+                warn_lamports: sol_to_lamports(balance_warn_sol),
+                floor_lamports: sol_to_lamports(balance_floor_sol),
+            },
+            alert_webhook_url,
+            alert_webhook_format,
+            alert_dedup_window: Duration::from_secs(alert_dedup_window_secs),
+            alert_failure_threshold,
+            schedules,
+            metrics_addr,
+            log_format,
+            explorer,
+            dry_run: cli.dry_run,
+        })
+    }
+}
+
+impl CrankConfig {
+    /// `rpc_url` followed by the failover endpoints, which derive their websocket endpoint since
+    /// `rpc_ws_url` belongs to `rpc_url`.
+    pub fn client_configs(&self) -> Vec<ClientConfig> {
+        let client_config = |rpc_url: &String, ws_url: Option<String>| ClientConfig {
+            rpc_url: rpc_url.clone(),
+            ws_url,
+            headers: self.rpc_headers.clone(),
+            timeout: self.rpc_timeout,
+            commitment: self.commitment,
+        };
+        let mut configs = vec![client_config(&self.rpc_url, self.rpc_ws_url.clone())];
+        configs.extend(self.rpc_failover_urls.iter().map(|url| client_config(url, None)));
+        configs
+    }
+
+    /// Interest period, in seconds, for savings vaults whose account does not set one.
+    pub fn fallback_interest_period(&self) -> i64 {
+        self.accrual_interval_days.saturating_mul(24 * 60 * 60)
+    }
+}
+
+fn expand_tilde(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path,
+    }
+}
+
+fn default_keypair_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/solana/id.json"))
+}
@@ -0,0 +1,805 @@
+use {
+    std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, Mutex},
+        time::Instant,
+    },
+    chrono::prelude::*,
+    solana_sdk::{
+        account::Account,
+        clock::UnixTimestamp,
+        native_token::lamports_to_sol,
+        pubkey::Pubkey,
+        signature::{read_keypair_file, Keypair, Signature},
+        signer::Signer,
+    },
+    solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient},
+    anyhow::{anyhow, bail, Context, Result},
+    tokio::sync::OnceCell,
+    tracing::{error, field, info, info_span, warn, Instrument, Span},
+    crate::{
+        alert::{Alert, AlertKind, AlertSink, Alerter, Severity, WebhookSink},
+        balance::{BalanceMonitor, BalanceReport, BalanceStatus},
+        batch::{compute_unit_limit, failed_instruction_index, pack_batches, AccrueBatch},
+        client::{get_cluster, redact_url, redact_urls, DetectedCluster},
+        config::{ConfigError, CrankConfig, MAX_COMPUTE_UNITS},
+        discovery::{discover_savings_vaults, DiscoveredVault},
+        error::CrankError,
+        explorer::ExplorerLinks,
+        metrics::Metrics,
+        outcome::CrankOutcome,
+        pda::find_savings_vault_pda,
+        preflight::{fetch_vault_accounts, validate_vaults, AccountProblem},
+        priority_fee::writable_accounts,
+        retry::is_retryable,
+        rpc_pool::RpcPool,
+        solvency::{check_treasuries, TreasuryCheck},
+        store::{AccrualRecord, CrankStore},
+        transaction::{accrue_interest_instruction, send_and_confirm, simulate, BatchSimulation},
+        vault_state::{fetch_clock, fetch_savings_vault, interest_rate_bps, VaultSchedule},
+    },
+};
+
+/// Discovers savings vaults and cranks `AccrueInterest` on them with a single cranker keypair.
+pub struct Cranker {
+    rpc: RpcPool,
+    keypair: Keypair,
+    config: CrankConfig,
+    metrics: Arc<Metrics>,
+    balance: Mutex<BalanceMonitor>,
+    alerts: Alerter,
+    cluster: OnceCell<DetectedCluster>,
+}
+
+impl Cranker {
+    pub fn new(config: CrankConfig, keypair: Keypair) -> Result<Self> {
+        let rpc = RpcPool::new(&config.client_configs(), config.rpc_max_slot_lag)?;
+
+        let balance = Mutex::new(BalanceMonitor::new(config.balance));
+        let alerts = Alerter::new(config.alert_dedup_window, config.alert_failure_threshold);
+
+        Ok(Self {
+            rpc,
+            keypair,
+            config,
+            metrics: Arc::new(Metrics::new()),
+            balance,
+            alerts,
+            cluster: OnceCell::new(),
+        })
+    }
+
+    /// Creates a cranker signing with the keypair at `config.keypair_path`, alerting to
+    /// `config.alert_webhook_url` when set.
+    pub fn from_config(config: CrankConfig) -> Result<Self> {
+        // Checked here rather than when loading, so commands without a cranker run without a keypair.
+        if !config.keypair_path.is_file() {
+            return Err(ConfigError::new(
+                "keypair_path",
+                format!("{} is not a file", config.keypair_path.display()),
+            )
+            .into());
+        }
+        let keypair = read_keypair_file(&config.keypair_path)
+            .map_err(|err| anyhow!("Failed to read keypair {}: {}", config.keypair_path.display(), err))?;
+        let webhook = match &config.alert_webhook_url {
+            Some(url) => Some(WebhookSink::new(url.clone(), config.alert_webhook_format)?),
+            None => None,
+        };
+
+        let mut cranker = Self::new(config, keypair)?;
+        if let Some(webhook) = webhook {
+            cranker = cranker.with_alert_sink(Box::new(webhook));
+        }
+        Ok(cranker)
+    }
+
+    /// Adds a destination for alerts raised while cranking.
+    pub fn with_alert_sink(mut self, sink: Box<dyn AlertSink>) -> Self {
+        self.alerts.add_sink(sink);
+        self
+    }
+
+    pub fn config(&self) -> &CrankConfig {
+        &self.config
+    }
+
+    /// Client of the currently healthiest RPC endpoint.
+    pub fn rpc(&self) -> &RpcClient {
+        self.rpc.client()
+    }
+
+    pub fn rpc_pool(&self) -> &RpcPool {
+        &self.rpc
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    pub fn alerts(&self) -> &Alerter {
+        &self.alerts
+    }
+
+    /// Cluster the RPC endpoints serve, detected once.
+    pub async fn cluster(&self) -> Result<DetectedCluster> {
+        self.cluster.get_or_try_init(|| get_cluster(self.rpc())).await.copied()
+    }
+
+    /// Detects the cluster of every RPC endpoint and fails unless they all serve the same one and
+    /// it matches `config.cluster` when declared.
+    pub async fn verify_cluster(&self) -> Result<DetectedCluster> {
+        let mut detected: Option<(DetectedCluster, String)> = None;
+        for endpoint in self.rpc.endpoints() {
+            let url = redact_url(&endpoint.url);
+            let cluster = get_cluster(endpoint.client())
+                .await
+                .map_err(|err| anyhow!(redact_urls(&format!("{:#}", err))))
+                .with_context(|| format!("Failed to detect the cluster of {}", url))?;
+            if let Some(declared) = &self.config.cluster {
+                if !cluster.matches(declared) {
+                    bail!(
+                        "RPC endpoint {} serves {} but the configuration declares {}, refusing to crank",
+                        url,
+                        cluster,
+                        declared
+                    );
+                }
+            }
+            match &detected {
+                Some((first, first_url)) if *first != cluster => bail!(
+                    "RPC endpoints disagree on the cluster: {} serves {} but {} serves {}",
+                    first_url,
+                    first,
+                    url,
+                    cluster
+                ),
+                Some(_) => {}
+                None => detected = Some((cluster, url)),
+            }
+        }
+
+        let (cluster, _) = detected.ok_or_else(|| anyhow!("No RPC endpoint configured"))?;
+        let _ = self.cluster.set(cluster);
+        Ok(cluster)
+    }
+
+    /// Explorer links for the detected cluster, pointing at the current RPC endpoint when the
+    /// cluster is not one the explorer knows.
+    pub async fn explorer_links(&self) -> ExplorerLinks {
+        ExplorerLinks::new(
+            self.config.explorer,
+            self.cluster().await.ok(),
+            self.rpc.current().url.clone(),
+        )
+    }
+
+    /// Status of the cranker balance against the configured policy, without the logging and
+    /// alerting of `check_balance`.
+    pub async fn balance_status(&self) -> Result<BalanceStatus> {
+        let lamports = self.rpc().get_balance(&self.pubkey()).await;
+        self.rpc.record(lamports.is_ok());
+        let lamports = lamports?;
+        self.metrics.set_cranker_balance(lamports);
+
+        Ok(self.config.balance.status(lamports))
+    }
+
+    /// Reads the cranker's balance, logs a warning when it is low and reports whether cranking
+    /// must pause because it fell below the configured floor.
+    pub async fn check_balance(&self) -> Result<BalanceReport> {
+        let lamports = self.rpc().get_balance(&self.pubkey()).await;
+        self.rpc.record(lamports.is_ok());
+        let lamports = lamports?;
+        let report = self
+            .balance
+            .lock()
+            .map_err(|_| anyhow!("Balance monitor lock poisoned"))?
+            .observe(lamports);
+
+        self.metrics.set_cranker_balance(lamports);
+        if let Some(runway) = report.runway {
+            self.metrics.set_cranker_runway(runway);
+        }
+        let runway_days = report.runway.map(|runway| runway.as_secs_f64() / (24.0 * 60.0 * 60.0));
+        match report.status {
+            BalanceStatus::Healthy => {}
+            BalanceStatus::Low => warn!(
+                cranker = %self.pubkey(),
+                balance_sol = lamports_to_sol(lamports),
+                warn_sol = lamports_to_sol(self.config.balance.warn_lamports),
+                runway_days,
+                "cranker balance is low"
+            ),
+            BalanceStatus::BelowFloor => error!(
+                cranker = %self.pubkey(),
+                balance_sol = lamports_to_sol(lamports),
+                floor_sol = lamports_to_sol(self.config.balance.floor_lamports),
+                "cranker balance is below the floor, pausing cranks until it is topped up"
+            ),
+        }
+        if report.status != BalanceStatus::Healthy {
+            let (severity, threshold) = match report.status {
+                BalanceStatus::BelowFloor => (Severity::Critical, self.config.balance.floor_lamports),
+                _ => (Severity::Warning, self.config.balance.warn_lamports),
+            };
+            let runway = runway_days.map_or_else(String::new, |days| format!(", about {:.1} days of runway", days));
+            let links = self.explorer_links().await;
+            self.alerts.alert(Alert {
+                kind: AlertKind::LowBalance,
+                severity,
+                subject: self.pubkey().to_string(),
+                message: format!(
+                    "{} SOL left, below {} SOL{} ({})",
+                    lamports_to_sol(lamports),
+                    lamports_to_sol(threshold),
+                    runway,
+                    links.account(&self.pubkey())
+                ),
+            });
+        }
+        Ok(report)
+    }
+
+    pub async fn discover(&self) -> Result<Vec<DiscoveredVault>> {
+        let vaults = discover_savings_vaults(self.rpc(), &self.config.program_id).await;
+        self.rpc.record(vaults.is_ok());
+        vaults
+    }
+
+    /// Reads the savings vault of `wallet` and `mint`, `None` when it does not exist.
+    pub async fn fetch_vault(&self, wallet: &Pubkey, mint: &Pubkey) -> Result<Option<DiscoveredVault>> {
+        let savings_vault = find_savings_vault_pda(&self.config.program_id, mint, wallet).0;
+        let state = match fetch_savings_vault(self.rpc(), &savings_vault).await? {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+
+        Ok(Some(DiscoveredVault {
+            savings_vault,
+            wallet: *wallet,
+            mint: *mint,
+            schedule: VaultSchedule::from_account(&state),
+            interest_rate_bps: interest_rate_bps(&state),
+        }))
+    }
+
+    /// Next time, on the cluster's clock, `vault` should be cranked: when its mint's configured
+    /// schedule fires, but never before the vault's own interest period has elapsed.
+    pub fn next_due(&self, vault: &DiscoveredVault) -> UnixTimestamp {
+        let scheduled = self
+            .config
+            .schedules
+            .get(&vault.mint)
+            .and_then(|schedule| schedule.next_after(vault.schedule.last_accrued_at));
+        match (scheduled, vault.schedule.period_due()) {
+            (Some(scheduled), Some(period_due)) => scheduled.max(period_due),
+            (Some(scheduled), None) => scheduled,
+            (None, _) => vault.schedule.next_due(self.config.fallback_interest_period()),
+        }
+    }
+
+    /// Discovers every savings vault, cranks those that are due on the cluster's clock and records the ones that accrued in `store`.
+    pub async fn run_cycle(&self, store: &CrankStore) -> Result<Vec<(DiscoveredVault, CrankOutcome)>> {
+        // A dry run pays no fees, so only real cranks are held back by the balance floor.
+        if !self.config.dry_run && self.check_balance().await?.status == BalanceStatus::BelowFloor {
+            return Ok(Vec::new());
+        }
+        let mut vaults = self.discover().await?;
+        for vault in &mut vaults {
+            self.apply_recorded_accrual(store, vault);
+        }
+        let now = fetch_clock(self.rpc()).await?.unix_timestamp;
+        let due_vaults: Vec<DiscoveredVault> = vaults
+            .into_iter()
+            .filter(|vault| now >= self.next_due(vault))
+            .collect();
+        if due_vaults.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let outcomes = self.crank_accrue_interest_batched(due_vaults, None).await;
+        self.record_outcomes(store, &outcomes).await;
+
+        Ok(outcomes)
+    }
+
+    /// Moves the last accrual of `vault` forward to the one recorded in `store` when that is
+    /// later, so a vault read from a lagging RPC node right after a successful crank is not
+    /// considered due again.
+    pub fn apply_recorded_accrual(&self, store: &CrankStore, vault: &mut DiscoveredVault) {
+        match store.last_accrual(&vault.savings_vault) {
+            Ok(Some(record)) => {
+                let recorded = record.last_accrued_at.timestamp();
+                vault.schedule.last_accrued_at = vault.schedule.last_accrued_at.max(recorded);
+            }
+            Ok(None) => {}
+            Err(err) => warn!(savings_vault = %vault.savings_vault, error = %err, "failed to read crank state"),
+        }
+    }
+
+    /// Records every successful accrual of `outcomes` in `store`, stamped with the cluster time
+    /// of the slot it landed in.
+    pub async fn record_outcomes(&self, store: &CrankStore, outcomes: &[(DiscoveredVault, CrankOutcome)]) {
+        for (vault, outcome) in outcomes {
+            if let CrankOutcome::Success { signature, slot } = outcome {
+                self.record_accrual(store, &vault.wallet, &vault.mint, signature, *slot).await;
+            }
+        }
+    }
+
+    /// Records in `store` that the savings vault of `wallet` and `mint` accrued in `signature`,
+    /// stamped with the cluster time of `slot`.
+    pub async fn record_accrual(
+        &self,
+        store: &CrankStore,
+        wallet: &Pubkey,
+        mint: &Pubkey,
+        signature: &Signature,
+        slot: u64,
+    ) {
+        let savings_vault = find_savings_vault_pda(&self.config.program_id, mint, wallet).0;
+        let last_accrued_at = match self.slot_time(slot).await {
+            Ok(last_accrued_at) => last_accrued_at,
+            Err(err) => {
+                warn!(savings_vault = %savings_vault, error = %err, "failed to read accrual time");
+                return;
+            }
+        };
+        let record = AccrualRecord {
+            savings_vault,
+            wallet: *wallet,
+            mint: *mint,
+            last_accrued_at,
+            signature: *signature,
+            slot,
+        };
+        if let Err(err) = store.record_accrual(&record) {
+            warn!(savings_vault = %savings_vault, error = %err, "failed to persist crank state");
+        }
+    }
+
+    /// Checks per mint whether the interest depositor treasury covers the next accrual of all of
+    /// `due_vaults`, for callers that crank them over several `crank_accrue_interest_batched` calls.
+    pub async fn check_solvency(&self, due_vaults: &[DiscoveredVault]) -> Result<HashMap<Pubkey, TreasuryCheck>> {
+        let accounts = fetch_vault_accounts(self.rpc(), &self.config.program_id, due_vaults).await;
+        self.rpc.record(accounts.is_ok());
+        Ok(check_treasuries(&self.config.program_id, due_vaults, &accounts?))
+    }
+
+    /// Cluster time of `slot`, falling back to the `Clock` sysvar while the block time of a
+    /// freshly confirmed slot is not available yet.
+    async fn slot_time(&self, slot: u64) -> Result<DateTime<Utc>> {
+        let timestamp = match self.rpc().get_block_time(slot).await {
+            Ok(timestamp) => timestamp,
+            Err(_) => fetch_clock(self.rpc()).await?.unix_timestamp,
+        };
+        Utc.timestamp_opt(timestamp, 0)
+            .single()
+            .ok_or_else(|| anyhow!("Invalid block time {} for slot {}", timestamp, slot))
+    }
+
+    /// Cranks the savings vault of `wallet` and `mint`, returning the `Success` or `Simulated`
+    /// outcome, or why it was not cranked.
+    pub async fn crank_accrue_interest(&self, wallet: &Pubkey, mint: &Pubkey) -> Result<CrankOutcome, CrankError> {
+        let savings_vault = find_savings_vault_pda(&self.config.program_id, mint, wallet).0;
+        let vault = match self.fetch_vault(wallet, mint).await {
+            Ok(Some(vault)) => vault,
+            Ok(None) => return Err(self.vault_not_found(&savings_vault).await),
+            // Anything but an RPC failure means the account exists but does not deserialize.
+            Err(err) => {
+                return Err(match err.downcast_ref::<ClientError>() {
+                    Some(err) => CrankError::from_client_error(err),
+                    None => CrankError::InvalidAccount {
+                        name: "savings vault",
+                        account: savings_vault,
+                        problem: AccountProblem::Malformed,
+                    },
+                })
+            }
+        };
+
+        match self.crank_accrue_interest_batched(vec![vault], None).await.remove(0).1 {
+            CrankOutcome::Failed { error, .. } => Err(error),
+            outcome => Ok(outcome),
+        }
+    }
+
+    /// Cranks every vault in `vaults`, packing as many `AccrueInterest` instructions per
+    /// transaction as fit, and returns one outcome per vault. Vaults whose accounts fail
+    /// pre-flight validation, or whose interest depositor treasury cannot cover the interest owed,
+    /// are skipped without sending.
+    ///
+    /// Callers that split the due vaults of a mint across several calls pass the `solvency`
+    /// checks computed over all of them, see `check_solvency`, so the batches do not each count
+    /// the full treasury balance against their own share.
+    pub async fn crank_accrue_interest_batched(
+        &self,
+        vaults: Vec<DiscoveredVault>,
+        solvency: Option<&HashMap<Pubkey, TreasuryCheck>>,
+    ) -> Vec<(DiscoveredVault, CrankOutcome)> {
+        let config = &self.config;
+        let links = self.explorer_links().await;
+        let mut outcomes = Vec::with_capacity(vaults.len());
+        let (vaults, accounts) = self.skip_invalid(vaults, &links, &mut outcomes).await;
+        let vaults = self.skip_underfunded(vaults, solvency, &accounts, &links, &mut outcomes);
+        let accruals = vaults
+            .into_iter()
+            .map(|vault| {
+                let accrue_ix =
+                    accrue_interest_instruction(&config.program_id, &self.pubkey(), &vault.wallet, &vault.mint);
+                (vault, accrue_ix)
+            })
+            .collect::<Vec<_>>();
+
+        let mut pending = pack_batches(&self.pubkey(), config.max_batch_size, accruals);
+        while let Some(batch) = pending.pop() {
+            let span = batch_span(&batch);
+            self.crank_batch(batch, &links, &mut pending, &mut outcomes)
+                .instrument(span)
+                .await;
+        }
+        for (vault, outcome) in &outcomes {
+            self.metrics.observe_outcome(&vault.mint, outcome);
+            self.alerts.observe_outcome(vault, outcome, &links);
+        }
+
+        outcomes
+    }
+
+    /// Simulates and sends one batch, pushing its outcomes, or the smaller batches to retry when
+    /// the simulation or the sent transaction blamed one of its vaults, or the simulation could
+    /// not size it.
+    async fn crank_batch(
+        &self,
+        mut batch: AccrueBatch,
+        links: &ExplorerLinks,
+        pending: &mut Vec<AccrueBatch>,
+        outcomes: &mut Vec<(DiscoveredVault, CrankOutcome)>,
+    ) {
+        let config = &self.config;
+        let compute_units = match self.simulate_batch(&batch).await {
+            BatchSimulation::Succeeded { units_consumed: Some(units_consumed) } => {
+                let per_vault = units_consumed / batch.len() as u64;
+                for vault in &batch.vaults {
+                    self.metrics.observe_compute_units(&vault.mint, per_vault);
+                }
+                compute_unit_limit(units_consumed, config.compute_unit_margin)
+            }
+            BatchSimulation::Failed { error, logs } => {
+                // Peel off the vault the simulation blamed and try the rest again, or halve
+                // the batch when the failure is not tied to a single instruction.
+                match failed_instruction_index(&error).and_then(|index| batch.vault_index(index)) {
+                    Some(index) => {
+                        let (vault, _) = batch.remove(index);
+                        if config.dry_run {
+                            log_failed_simulation(&vault, &logs);
+                        }
+                        let outcome = CrankOutcome::from_transaction_error(None, error, &logs);
+                        log_outcome(&vault, &outcome, None, links);
+                        outcomes.push((vault, outcome));
+                        if !batch.is_empty() {
+                            pending.push(batch);
+                        }
+                    }
+                    None if batch.len() > 1 => {
+                        let (first, second) = batch.split();
+                        pending.push(first);
+                        pending.push(second);
+                    }
+                    None => {
+                        let vault = batch.vaults[0];
+                        if config.dry_run {
+                            log_failed_simulation(&vault, &logs);
+                        }
+                        let outcome = CrankOutcome::from_transaction_error(None, error, &logs);
+                        log_outcome(&vault, &outcome, None, links);
+                        outcomes.push((vault, outcome));
+                    }
+                }
+                return;
+            }
+            BatchSimulation::Succeeded { units_consumed: None } | BatchSimulation::Unavailable => {
+                let fallback = u64::from(config.compute_units) * batch.len() as u64;
+                if fallback > u64::from(MAX_COMPUTE_UNITS) && batch.len() > 1 {
+                    let (first, second) = batch.split();
+                    pending.push(first);
+                    pending.push(second);
+                    return;
+                }
+                fallback as u32
+            }
+        };
+
+        let (outcome, attempt) = if config.dry_run {
+            (self.dry_run_batch(&batch, compute_units).await, None)
+        } else {
+            let (outcome, attempt) = self.send_with_retry(&batch, compute_units).await;
+            (outcome, Some(attempt))
+        };
+        // Like a failed simulation, a failure blamed on one instruction only settles its vault;
+        // the rest of the batch did not run and goes back to be sent without it.
+        let blamed = outcome
+            .error()
+            .and_then(CrankError::instruction_index)
+            .and_then(|index| batch.vault_index(index));
+        if let Some(index) = blamed {
+            let (vault, _) = batch.remove(index);
+            log_outcome(&vault, &outcome, attempt, links);
+            outcomes.push((vault, outcome));
+            if !batch.is_empty() {
+                pending.push(batch);
+            }
+            return;
+        }
+        for vault in batch.vaults {
+            log_outcome(&vault, &outcome, attempt, links);
+            outcomes.push((vault, outcome.clone()));
+        }
+    }
+
+    /// Returns the vaults whose accounts pass `validate_vaults`, pushing a failed outcome for every
+    /// other one, along with the accounts read so later checks need no further read. When the
+    /// accounts cannot be read no vault is returned, since none was validated.
+    async fn skip_invalid(
+        &self,
+        vaults: Vec<DiscoveredVault>,
+        links: &ExplorerLinks,
+        outcomes: &mut Vec<(DiscoveredVault, CrankOutcome)>,
+    ) -> (Vec<DiscoveredVault>, HashMap<Pubkey, Account>) {
+        let cluster = self.cluster().await.ok();
+        let accounts = fetch_vault_accounts(self.rpc(), &self.config.program_id, &vaults).await;
+        self.rpc.record(accounts.is_ok());
+        let accounts = match accounts {
+            Ok(accounts) => accounts,
+            Err(err) => {
+                let outcome = CrankOutcome::rpc_failure(&err);
+                for vault in vaults {
+                    log_outcome(&vault, &outcome, None, links);
+                    outcomes.push((vault, outcome.clone()));
+                }
+                return (Vec::new(), HashMap::new());
+            }
+        };
+        let results = validate_vaults(&self.config.program_id, &vaults, &accounts, cluster);
+
+        let mut valid = Vec::with_capacity(vaults.len());
+        for (vault, result) in vaults.into_iter().zip(results) {
+            match result {
+                Ok(()) => valid.push(vault),
+                Err(error) => {
+                    let outcome = CrankOutcome::failed(None, error);
+                    log_outcome(&vault, &outcome, None, links);
+                    outcomes.push((vault, outcome));
+                }
+            }
+        }
+        (valid, accounts)
+    }
+
+    /// Returns the vaults whose interest depositor treasury covers what their mint's due vaults owe,
+    /// pushing a `TreasuryUnderfunded` outcome for every other one.
+    ///
+    /// `solvency` holds the checks computed over every due vault of each mint; without it only
+    /// `vaults` themselves are counted, from the `accounts` pre-flight already read.
+    fn skip_underfunded(
+        &self,
+        vaults: Vec<DiscoveredVault>,
+        solvency: Option<&HashMap<Pubkey, TreasuryCheck>>,
+        accounts: &HashMap<Pubkey, Account>,
+        links: &ExplorerLinks,
+        outcomes: &mut Vec<(DiscoveredVault, CrankOutcome)>,
+    ) -> Vec<DiscoveredVault> {
+        let computed;
+        let checks = match solvency {
+            Some(checks) => checks,
+            None => {
+                computed = check_treasuries(&self.config.program_id, &vaults, accounts);
+                &computed
+            }
+        };
+        let mints: HashSet<Pubkey> = vaults.iter().map(|vault| vault.mint).collect();
+        let checks: HashMap<Pubkey, TreasuryCheck> = checks
+            .iter()
+            .filter(|(mint, _)| mints.contains(mint))
+            .map(|(mint, check)| (*mint, *check))
+            .collect();
+        for check in checks.values().filter(|check| check.is_underfunded()) {
+            error!(
+                mint = %check.mint,
+                interest_depositor_treasury = %check.interest_depositor_treasury,
+                balance = check.balance,
+                owed = check.owed,
+                "interest depositor treasury is underfunded, skipping its vaults"
+            );
+            self.alerts.alert(Alert {
+                kind: AlertKind::TreasuryUnderfunded,
+                severity: Severity::Critical,
+                subject: check.mint.to_string(),
+                message: format!(
+                    "treasury {} holds {} but due vaults owe about {} ({})",
+                    check.interest_depositor_treasury,
+                    check.balance,
+                    check.owed,
+                    links.account(&check.interest_depositor_treasury)
+                ),
+            });
+        }
+
+        let (funded, underfunded): (Vec<_>, Vec<_>) = vaults
+            .into_iter()
+            .partition(|vault| !checks.get(&vault.mint).map_or(false, TreasuryCheck::is_underfunded));
+        for vault in underfunded {
+            let check = &checks[&vault.mint];
+            let outcome = CrankOutcome::failed(
+                None,
+                CrankError::TreasuryUnderfunded {
+                    interest_depositor_treasury: check.interest_depositor_treasury,
+                    balance: check.balance,
+                    owed: check.owed,
+                },
+            );
+            log_outcome(&vault, &outcome, None, links);
+            outcomes.push((vault, outcome));
+        }
+        funded
+    }
+
+    /// Simulates `batch` with the maximum compute unit limit so `units_consumed` reflects what
+    /// the batch actually needs.
+    async fn simulate_batch(&self, batch: &AccrueBatch) -> BatchSimulation {
+        let instructions = batch.transaction_instructions(MAX_COMPUTE_UNITS, 0);
+        match simulate(self.rpc(), self.config.commitment, &self.pubkey(), &instructions).await {
+            Ok(result) => result.into(),
+            Err(_) => BatchSimulation::Unavailable,
+        }
+    }
+
+    /// Simulates the exact transaction `send_with_retry` would send first and prints its logs.
+    async fn dry_run_batch(&self, batch: &AccrueBatch, compute_units: u32) -> CrankOutcome {
+        let writable = writable_accounts(&batch.instructions, &self.pubkey());
+        let recent_fee = self.config.priority_fee.recent_fee(self.rpc(), &writable).await.unwrap_or(0);
+        let compute_unit_price = self.config.priority_fee.price_for_attempt(recent_fee, 1);
+        let instructions = batch.transaction_instructions(compute_units, compute_unit_price);
+        let result = match simulate(self.rpc(), self.config.commitment, &self.pubkey(), &instructions).await {
+            Ok(result) => result,
+            Err(err) => return CrankOutcome::rpc_failure(&err),
+        };
+
+        info!(compute_unit_limit = compute_units, compute_unit_price, "simulated accrual");
+        for log in result.logs.as_deref().unwrap_or_default() {
+            info!(log = %log, "simulation log");
+        }
+
+        match result.err {
+            Some(error) => CrankOutcome::from_transaction_error(None, error, result.logs.as_deref().unwrap_or_default()),
+            None => CrankOutcome::Simulated {
+                units_consumed: result.units_consumed,
+                compute_unit_limit: compute_units,
+                compute_unit_price,
+            },
+        }
+    }
+
+    /// Sends `batch` until it succeeds, fails permanently or runs out of attempts, bidding a higher
+    /// compute unit price on every retry. Returns the final outcome and the attempt that produced it.
+    async fn send_with_retry(&self, batch: &AccrueBatch, compute_units: u32) -> (CrankOutcome, u32) {
+        let config = &self.config;
+        let writable = writable_accounts(&batch.instructions, &self.pubkey());
+        let mut attempt = 1;
+        loop {
+            // Without fee data the floor price still goes out and escalates on retries.
+            let recent_fee = config.priority_fee.recent_fee(self.rpc(), &writable).await.unwrap_or(0);
+            let compute_unit_price = config.priority_fee.price_for_attempt(recent_fee, attempt);
+            let instructions = batch.transaction_instructions(compute_units, compute_unit_price);
+
+            for vault in &batch.vaults {
+                self.metrics.observe_attempt(&vault.mint);
+            }
+            let span = info_span!(
+                "accrue_attempt",
+                vaults = batch.len(),
+                attempt,
+                compute_unit_price,
+                signature = field::Empty
+            );
+            let sent_at = Instant::now();
+            let endpoint = self.rpc.current();
+            let outcome = send_and_confirm(endpoint.client(), &endpoint.ws_url, config, &self.keypair, &instructions)
+                .instrument(span.clone())
+                .await;
+            self.rpc.record(!matches!(
+                outcome.error(),
+                Some(CrankError::RpcUnavailable(_) | CrankError::RpcError(_))
+            ));
+            if let Some(signature) = outcome.signature() {
+                span.record("signature", field::display(signature));
+            }
+            if let CrankOutcome::Success { .. } = outcome {
+                for vault in &batch.vaults {
+                    self.metrics.observe_confirmation(&vault.mint, sent_at.elapsed());
+                }
+            }
+            if attempt >= config.retry.max_attempts || !is_retryable(&outcome) {
+                return (outcome, attempt);
+            }
+            let delay = config.retry.delay_for(attempt);
+            span.in_scope(|| warn!(retry_in = ?delay, outcome = %outcome, "accrue attempt failed, retrying"));
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn vault_not_found(&self, savings_vault: &Pubkey) -> CrankError {
+        CrankError::VaultNotFound {
+            savings_vault: *savings_vault,
+            cluster: self.cluster().await.ok(),
+        }
+    }
+}
+
+/// Span covering the simulation, sending and confirmation of `batch`, listing its vaults.
+fn batch_span(batch: &AccrueBatch) -> Span {
+    let list = |key: fn(&DiscoveredVault) -> Pubkey| {
+        batch.vaults.iter().map(|vault| key(vault).to_string()).collect::<Vec<_>>().join(",")
+    };
+    info_span!(
+        "crank",
+        vaults = batch.len(),
+        wallets = %list(|vault| vault.wallet),
+        mints = %list(|vault| vault.mint),
+        savings_vaults = %list(|vault| vault.savings_vault)
+    )
+}
+
+/// Prints the program logs of a dry-run simulation that failed on `vault`, which explain why.
+fn log_failed_simulation(vault: &DiscoveredVault, logs: &[String]) {
+    for log in logs {
+        info!(savings_vault = %vault.savings_vault, log = %log, "simulation log");
+    }
+}
+
+/// Logs the outcome of cranking `vault` with its accounts, the transaction signature and the
+/// send attempt that produced it, if any, and an explorer link to the transaction or, without
+/// one, the savings vault.
+fn log_outcome(vault: &DiscoveredVault, outcome: &CrankOutcome, attempt: Option<u32>, links: &ExplorerLinks) {
+    let signature = outcome.signature();
+    let explorer_url = match signature {
+        Some(signature) => links.transaction(&signature),
+        None => links.account(&vault.savings_vault),
+    };
+    let signature = signature.map(|signature| signature.to_string());
+    match outcome {
+        CrankOutcome::Failed { error, .. } if !matches!(error, CrankError::AlreadyAccrued(_)) => warn!(
+            wallet = %vault.wallet,
+            mint = %vault.mint,
+            savings_vault = %vault.savings_vault,
+            signature,
+            attempt,
+            explorer_url,
+            class = error.class(),
+            retryable = error.is_retryable(),
+            error = %error,
+            "crank did not succeed"
+        ),
+        _ => info!(
+            wallet = %vault.wallet,
+            mint = %vault.mint,
+            savings_vault = %vault.savings_vault,
+            signature,
+            attempt,
+            explorer_url,
+            outcome = %outcome,
+            "{}",
+            match outcome {
+                CrankOutcome::Success { .. } => "accrued interest",
+                CrankOutcome::Simulated { .. } => "dry run",
+                CrankOutcome::Failed { .. } => "interest already accrued",
+            }
+        ),
+    }
+}
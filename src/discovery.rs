@@ -0,0 +1,66 @@
+use {
+    solana_sdk::pubkey::Pubkey,
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        nonblocking::rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    anchor_client::anchor_lang::{AccountDeserialize, Discriminator},
+    anyhow::Result,
+    savings_vault::state::SavingsVault,
+    crate::{
+        pda::find_savings_vault_pda,
+        vault_state::{interest_rate_bps, VaultSchedule},
+    },
+};
+
+/// A savings vault account found on chain together with the wallet and mint it belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DiscoveredVault {
+    pub savings_vault: Pubkey,
+    pub wallet: Pubkey,
+    pub mint: Pubkey,
+    pub schedule: VaultSchedule,
+    /// Interest paid per accrual, in basis points of the vault's treasury balance.
+    pub interest_rate_bps: u64,
+}
+
+/// Enumerates every `SavingsVault` account owned by the savings vault program.
+///
+/// Accounts that fail to deserialize, or that do not sit at the PDA derived from their own
+/// wallet and mint, are skipped since `AccrueInterest` would reject them anyway.
+pub async fn discover_savings_vaults(rpc_client: &RpcClient, program_id: &Pubkey) -> Result<Vec<DiscoveredVault>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            0,
+            &SavingsVault::discriminator(),
+        ))]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        with_context: None,
+    };
+    let accounts = rpc_client.get_program_accounts_with_config(program_id, config).await?;
+
+    let mut vaults = Vec::with_capacity(accounts.len());
+    for (savings_vault, account) in accounts {
+        let state = match SavingsVault::try_deserialize(&mut account.data.as_slice()) {
+            Ok(state) => state,
+            Err(_) => continue,
+        };
+        if find_savings_vault_pda(program_id, &state.mint, &state.wallet).0 != savings_vault {
+            continue;
+        }
+        vaults.push(DiscoveredVault {
+            savings_vault,
+            wallet: state.wallet,
+            mint: state.mint,
+            schedule: VaultSchedule::from_account(&state),
+            interest_rate_bps: interest_rate_bps(&state),
+        });
+    }
+
+    Ok(vaults)
+}
@@ -0,0 +1,238 @@
+use {
+    std::fmt,
+    solana_sdk::{pubkey::Pubkey, transaction::TransactionError},
+    solana_client::client_error::ClientError,
+    crate::{
+        batch::failed_instruction_index,
+        client::{redact_urls, DetectedCluster},
+        outcome::ProgramErrorInfo,
+        preflight::AccountProblem,
+        retry::{is_retryable_client_error, is_retryable_transaction_error},
+    },
+};
+
+/// Why a savings vault was not cranked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CrankError {
+    /// The savings vault account does not exist.
+    VaultNotFound { savings_vault: Pubkey, cluster: Option<DetectedCluster> },
+    /// Not sent: an account `AccrueInterest` reads failed pre-flight validation.
+    InvalidAccount { name: &'static str, account: Pubkey, problem: AccountProblem },
+    /// The vault's interest period has not elapsed since its last accrual.
+    AlreadyAccrued(ProgramErrorInfo),
+    /// Not sent: the interest depositor treasury holds less than the interest the due vaults of
+    /// its mint are estimated to owe.
+    TreasuryUnderfunded { interest_depositor_treasury: Pubkey, balance: u64, owed: u64 },
+    /// The program refused to accrue because the interest depositor treasury cannot pay out.
+    InsufficientTreasury(ProgramErrorInfo),
+    /// Any other savings_vault or CPI error.
+    ProgramError(ProgramErrorInfo),
+    /// The blockhash the transaction was signed with expired before it landed.
+    BlockhashExpired,
+    /// The runtime rejected the transaction before or while executing it.
+    TransactionError(TransactionError),
+    /// The RPC node could not be reached, rate limited us or is unhealthy.
+    RpcUnavailable(String),
+    /// The RPC node rejected the request.
+    RpcError(String),
+    /// The cranker keypair could not sign the transaction.
+    SignerError(String),
+    /// The transaction was sent but did not reach the commitment before the timeout.
+    Timeout,
+}
+
+impl CrankError {
+    /// Classifies a failed transaction, using program logs to name Anchor errors.
+    pub fn from_transaction_error(error: TransactionError, logs: &[String]) -> Self {
+        match ProgramErrorInfo::from_transaction_error(&error, logs) {
+            Some(error) if error.is_already_accrued() => Self::AlreadyAccrued(error),
+            Some(error) if error.is_insufficient_treasury() => Self::InsufficientTreasury(error),
+            Some(error) => Self::ProgramError(error),
+            None if error == TransactionError::BlockhashNotFound => Self::BlockhashExpired,
+            None => Self::TransactionError(error),
+        }
+    }
+
+    /// Index of the transaction instruction the error is tied to, if any, see
+    /// `failed_instruction_index`.
+    pub fn instruction_index(&self) -> Option<u8> {
+        match self {
+            Self::AlreadyAccrued(error) | Self::InsufficientTreasury(error) | Self::ProgramError(error) => {
+                Some(error.instruction_index)
+            }
+            Self::TransactionError(error) => failed_instruction_index(error),
+            _ => None,
+        }
+    }
+
+    /// Classifies an RPC error unrelated to the transaction's execution. HTTP errors name the
+    /// request URL, which is redacted since it often holds the provider's API key.
+    pub fn from_client_error(err: &ClientError) -> Self {
+        let message = redact_urls(&err.to_string());
+        if is_retryable_client_error(err) {
+            Self::RpcUnavailable(message)
+        } else {
+            Self::RpcError(message)
+        }
+    }
+
+    /// Whether trying again with a fresh blockhash can succeed.
+    ///
+    /// Program errors and missing accounts are permanent: resending the same instruction
+    /// against the same state fails the same way and only burns fees.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::BlockhashExpired | Self::RpcUnavailable(_) | Self::Timeout => true,
+            Self::TransactionError(error) => is_retryable_transaction_error(error),
+            Self::VaultNotFound { .. }
+            | Self::InvalidAccount { .. }
+            | Self::AlreadyAccrued(_)
+            | Self::TreasuryUnderfunded { .. }
+            | Self::InsufficientTreasury(_)
+            | Self::ProgramError(_)
+            | Self::RpcError(_)
+            | Self::SignerError(_) => false,
+        }
+    }
+
+    /// Short snake_case name of the error, used as the `error_class` metric label.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Self::VaultNotFound { .. } => "vault_not_found",
+            Self::InvalidAccount { .. } => "invalid_account",
+            Self::AlreadyAccrued(_) => "already_accrued",
+            Self::TreasuryUnderfunded { .. } => "treasury_underfunded",
+            Self::InsufficientTreasury(_) => "insufficient_treasury",
+            Self::ProgramError(_) => "program_error",
+            Self::BlockhashExpired => "blockhash_expired",
+            Self::TransactionError(_) => "transaction_error",
+            Self::RpcUnavailable(_) => "rpc_unavailable",
+            Self::RpcError(_) => "rpc_error",
+            Self::SignerError(_) => "signer_error",
+            Self::Timeout => "timeout",
+        }
+    }
+}
+
+impl fmt::Display for CrankError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VaultNotFound { savings_vault, cluster } => match cluster {
+                Some(cluster) => write!(f, "savings vault account {} does not exist on {}", savings_vault, cluster),
+                None => write!(f, "savings vault account {} does not exist", savings_vault),
+            },
+            Self::InvalidAccount { name, account, problem } => write!(f, "{} {} {}, not sent", name, account, problem),
+            Self::AlreadyAccrued(error) => write!(f, "interest already accrued: {}", error),
+            Self::TreasuryUnderfunded {
+                interest_depositor_treasury,
+                balance,
+                owed,
+            } => write!(
+                f,
+                "interest depositor treasury {} holds {} but due vaults owe about {}, not sent",
+                interest_depositor_treasury, balance, owed
+            ),
+            Self::InsufficientTreasury(error) => write!(f, "interest depositor treasury underfunded: {}", error),
+            Self::ProgramError(error) => write!(f, "program error: {}", error),
+            Self::BlockhashExpired => f.write_str("blockhash expired before the transaction landed"),
+            Self::TransactionError(error) => write!(f, "transaction error: {}", error),
+            Self::RpcUnavailable(error) => write!(f, "RPC unavailable: {}", error),
+            Self::RpcError(error) => write!(f, "RPC error: {}", error),
+            Self::SignerError(error) => write!(f, "failed to sign: {}", error),
+            Self::Timeout => f.write_str("transaction was not confirmed in time"),
+        }
+    }
+}
+
+impl std::error::Error for CrankError {}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_client::{
+            rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+            rpc_request::{RpcError, RpcResponseErrorData},
+        },
+        solana_sdk::instruction::InstructionError,
+        spl_token::error::TokenError,
+        savings_vault::error::ErrorCode,
+    };
+
+    fn custom(code: u32) -> TransactionError {
+        TransactionError::InstructionError(2, InstructionError::Custom(code))
+    }
+
+    fn rpc_error(code: i64) -> ClientError {
+        ClientError::from(RpcError::RpcResponseError {
+            code,
+            message: "rejected".to_string(),
+            data: RpcResponseErrorData::Empty,
+        })
+    }
+
+    #[test]
+    fn classifies_savings_vault_errors_as_permanent() {
+        let already_accrued = CrankError::from_transaction_error(custom(u32::from(ErrorCode::InterestAlreadyAccrued)), &[]);
+        assert!(matches!(already_accrued, CrankError::AlreadyAccrued(_)), "{:?}", already_accrued);
+        assert!(!already_accrued.is_retryable());
+
+        let insufficient =
+            CrankError::from_transaction_error(custom(u32::from(ErrorCode::InsufficientInterestFunds)), &[]);
+        assert!(matches!(insufficient, CrankError::InsufficientTreasury(_)), "{:?}", insufficient);
+        assert!(!insufficient.is_retryable());
+
+        let token = CrankError::from_transaction_error(custom(TokenError::InsufficientFunds as u32), &[]);
+        assert!(matches!(token, CrankError::InsufficientTreasury(_)), "{:?}", token);
+
+        let other = CrankError::from_transaction_error(custom(u32::MAX), &[]);
+        assert!(matches!(other, CrankError::ProgramError(_)), "{:?}", other);
+        assert!(!other.is_retryable());
+    }
+
+    #[test]
+    fn expired_blockhash_is_retryable() {
+        let error = CrankError::from_transaction_error(TransactionError::BlockhashNotFound, &[]);
+        assert_eq!(error, CrankError::BlockhashExpired);
+        assert!(error.is_retryable());
+
+        let error = CrankError::from_transaction_error(TransactionError::AccountNotFound, &[]);
+        assert_eq!(error, CrankError::TransactionError(TransactionError::AccountNotFound));
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn ties_errors_to_their_instruction() {
+        assert_eq!(CrankError::from_transaction_error(custom(u32::MAX), &[]).instruction_index(), Some(2));
+        let error = TransactionError::InstructionError(3, InstructionError::InvalidAccountData);
+        assert_eq!(CrankError::from_transaction_error(error, &[]).instruction_index(), Some(3));
+        assert_eq!(CrankError::BlockhashExpired.instruction_index(), None);
+        assert_eq!(CrankError::Timeout.instruction_index(), None);
+    }
+
+    #[test]
+    fn classifies_rpc_errors() {
+        let unhealthy = CrankError::from_client_error(&rpc_error(JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY));
+        assert!(matches!(unhealthy, CrankError::RpcUnavailable(_)), "{:?}", unhealthy);
+        assert!(unhealthy.is_retryable());
+
+        let rejected = CrankError::from_client_error(&rpc_error(-32602));
+        assert!(matches!(rejected, CrankError::RpcError(_)), "{:?}", rejected);
+        assert!(!rejected.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn client_errors_do_not_carry_the_rpc_url() {
+        let err = reqwest::Client::new()
+            .get("http://127.0.0.1:1/?api-key=SECRET")
+            .send()
+            .await
+            .unwrap_err();
+        let error = CrankError::from_client_error(&ClientError::from(err));
+
+        assert!(matches!(error, CrankError::RpcUnavailable(_)), "{:?}", error);
+        let message = error.to_string();
+        assert!(!message.contains("SECRET"), "{}", message);
+        assert!(message.contains("http://127.0.0.1:1"), "{}", message);
+    }
+}
@@ -0,0 +1,31 @@
+pub mod alert;
+pub mod balance;
+pub mod batch;
+pub mod client;
+pub mod config;
+pub mod cranker;
+pub mod discovery;
+pub mod error;
+pub mod explorer;
+pub mod logging;
+pub mod metrics;
+pub mod outcome;
+pub mod pda;
+pub mod preflight;
+pub mod priority_fee;
+pub mod retry;
+pub mod rpc_pool;
+pub mod schedule;
+pub mod scheduler;
+pub mod solvency;
+pub mod store;
+pub mod transaction;
+pub mod vault_state;
+
+pub use {
+    cranker::Cranker,
+    error::CrankError,
+    outcome::CrankOutcome,
+};
+
+pub const SAVINGS_VAULT_PROGRAM_ID: &str = "HfJVM6Ayjajt9H58AZoCFqkCQQFehSeQfGQbi3crxT8W";
@@ -1,202 +1,192 @@
 use {
-    std::{
-        rc::Rc,
-        str::FromStr, 
-        time::Duration,
-        thread::sleep,
-    },
+    std::sync::Arc,
     chrono::prelude::*,
-    solana_program::{
-        sysvar,
-    },
-    solana_sdk::{
-        signer::Signer,
-        pubkey::Pubkey,
-    },
-    solana_client::{
-        rpc_response::Response,
-        rpc_client::RpcClient,
-    },
-    anchor_client::{
-        solana_sdk::{
-            hash::Hash,
-            compute_budget::ComputeBudgetInstruction,
-            commitment_config::CommitmentConfig,
-            signature::{keypair::Keypair, read_keypair_file},
+    clap::Parser,
+    solana_sdk::{clock::UnixTimestamp, native_token::lamports_to_sol, pubkey::Pubkey},
+    anyhow::{anyhow, Result},
+    tracing::{error, info},
+    crank_interest::{
+        balance::BalanceStatus,
+        config::{Cli, Command, CrankConfig},
+        logging, metrics,
+        pda::{
+            find_interest_depositor_manager_pda, find_interest_depositor_treasury_pda, find_savings_vault_pda,
+            find_savings_vault_treasury_pda, VaultPdas,
         },
-        Client, Cluster,
+        scheduler::Scheduler,
+        store::CrankStore,
+        vault_state::fetch_clock,
+        CrankError, CrankOutcome, Cranker,
     },
-    anyhow::{anyhow, Result, Error},
-    savings_vault::accounts,
-    spl_token::ID as TOKEN_PROGRAM_ID,
 };
 
+#[tokio::main]
+async fn main() {
+    let mut cli = Cli::parse();
+    let command = cli.command.take().unwrap_or(Command::Run);
+    let config = match CrankConfig::load(cli) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{:#}", err);
+            std::process::exit(1);
+        }
+    };
+    logging::init(config.log_format);
 
-pub const SAVINGS_VAULT_PROGRAM_ID: &str = "HfJVM6Ayjajt9H58AZoCFqkCQQFehSeQfGQbi3crxT8W";
-
-pub const KEYPAIR_PATH: &str = "/Users/0xabstracted/.config/solana/id.json";
-pub const RPC_URL: &str = "https://api.devnet.solana.com";
-pub const COMPUTE_UNITS: u32 = 400_000;
-
-
-pub const SEED_SAVINGS_VAULT: &[u8] = b"savings_vault";
-pub const SEED_SAVINGS_VAULT_TREASURY: &[u8] = b"savings_vault-treasury";
-pub const SEED_INTEREST_DEPOSITOR_MANAGER: &[u8] = b"interest_depositor_manager";
-pub const SEED_INTEREST_DEPOSITOR_TREASURY: &[u8] = b"interest_depositor_treasury";
-
-pub struct ClientConfig {
-    pub keypair: Keypair,
-    pub rpc_url: String,
+    if let Err(err) = execute(command, config).await {
+        error!(error = format!("{:#}", err), "command failed");
+        std::process::exit(1);
+    }
 }
 
-pub type SavingsVaultClient = Client<Rc<Keypair>>;
-
-pub fn setup_client(config: &ClientConfig) -> Result<SavingsVaultClient> {
-    let rpc_url = config.rpc_url.clone();
-    let ws_url = rpc_url.replace("http", "ws");
-    let cluster = Cluster::Custom(rpc_url, ws_url);
-
-    let key_bytes = config.keypair.to_bytes();
-    let signer = Rc::new(Keypair::from_bytes(&key_bytes)?);
-
-    let opts = CommitmentConfig::confirmed();
-    Ok(Client::new_with_options(cluster, signer, opts))
+async fn execute(command: Command, config: CrankConfig) -> Result<()> {
+    match command {
+        Command::Derive { wallet, mint } => {
+            derive(&config.program_id, &wallet, &mint);
+            Ok(())
+        }
+        Command::Crank { wallet, mint } => crank(&connect(config).await?, &wallet, &mint).await,
+        Command::List => list(&connect(config).await?).await,
+        Command::Status => status(&connect(config).await?).await,
+        Command::Run => run(connect(config).await?).await,
+    }
 }
 
-
-pub fn find_savings_vault_pda(mint: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
-    let savings_vault_seeds = &[SEED_SAVINGS_VAULT, mint.as_ref(), wallet.as_ref()];
-    let savings_vault_program_key: Pubkey  = Pubkey::from_str(SAVINGS_VAULT_PROGRAM_ID).unwrap();
-
-    Pubkey::find_program_address(savings_vault_seeds, &savings_vault_program_key)
+/// Creates the cranker and refuses to go on unless its RPC endpoints serve the configured cluster.
+async fn connect(config: CrankConfig) -> Result<Arc<Cranker>> {
+    let cranker = Arc::new(Cranker::from_config(config)?);
+    let cluster = cranker.verify_cluster().await?;
+    info!(%cluster, "connected");
+    Ok(cranker)
 }
 
-
-pub fn find_savings_vault_treasury_pda(savings_vault: &Pubkey) -> (Pubkey, u8) {
-    let savings_vault_treasury_seeds = &[SEED_SAVINGS_VAULT_TREASURY, savings_vault.as_ref()];
-    let savings_vault_program_key: Pubkey  = Pubkey::from_str(SAVINGS_VAULT_PROGRAM_ID).unwrap();
-
-    Pubkey::find_program_address(savings_vault_treasury_seeds, &savings_vault_program_key)
+fn derive(program_id: &Pubkey, wallet: &Pubkey, mint: &Pubkey) {
+    let (savings_vault, savings_vault_bump) = find_savings_vault_pda(program_id, mint, wallet);
+    let (savings_vault_treasury, savings_vault_treasury_bump) =
+        find_savings_vault_treasury_pda(program_id, &savings_vault);
+    let (interest_depositor_manager, interest_depositor_manager_bump) =
+        find_interest_depositor_manager_pda(program_id, mint);
+    let (interest_depositor_treasury, interest_depositor_treasury_bump) =
+        find_interest_depositor_treasury_pda(program_id, &interest_depositor_manager);
+
+    println!("savings vault:               {} (bump {})", savings_vault, savings_vault_bump);
+    println!(
+        "savings vault treasury:      {} (bump {})",
+        savings_vault_treasury, savings_vault_treasury_bump
+    );
+    println!(
+        "interest depositor manager:  {} (bump {})",
+        interest_depositor_manager, interest_depositor_manager_bump
+    );
+    println!(
+        "interest depositor treasury: {} (bump {})",
+        interest_depositor_treasury, interest_depositor_treasury_bump
+    );
 }
 
-pub fn find_interest_depositor_manager_pda(mint: &Pubkey) -> (Pubkey, u8) {
-    let interest_depositor_manager_seeds = &[SEED_INTEREST_DEPOSITOR_MANAGER, mint.as_ref()];
-    let savings_vault_program_key: Pubkey  = Pubkey::from_str(SAVINGS_VAULT_PROGRAM_ID).unwrap();
+async fn crank(cranker: &Cranker, wallet: &Pubkey, mint: &Pubkey) -> Result<()> {
+    let store = CrankStore::open(&cranker.config().state_path)?;
+    let links = cranker.explorer_links().await;
+    let savings_vault = find_savings_vault_pda(&cranker.config().program_id, mint, wallet).0;
 
-    Pubkey::find_program_address(interest_depositor_manager_seeds, &savings_vault_program_key)
+    match cranker.crank_accrue_interest(wallet, mint).await {
+        Ok(CrankOutcome::Success { signature, slot }) => {
+            cranker.record_accrual(&store, wallet, mint, &signature, slot).await;
+            println!("Accrued interest for {}: {}", savings_vault, links.transaction(&signature));
+        }
+        Ok(outcome) => println!("Dry run for {}: {}", savings_vault, outcome),
+        Err(CrankError::VaultNotFound { savings_vault, .. }) => {
+            return Err(anyhow!("Savings vault {} does not exist ({})", savings_vault, links.account(&savings_vault)));
+        }
+        Err(err) => return Err(anyhow!("Crank of {} did not succeed: {}", savings_vault, err)),
+    }
+    Ok(())
 }
 
-pub fn find_interest_depositor_treasury_pda(interest_depositor_manager: &Pubkey) -> (Pubkey, u8) {
-    let interest_depositor_treasury_seeds = &[SEED_INTEREST_DEPOSITOR_TREASURY, interest_depositor_manager.as_ref()];
-    let savings_vault_program_key: Pubkey  = Pubkey::from_str(SAVINGS_VAULT_PROGRAM_ID).unwrap();
-
-    Pubkey::find_program_address(interest_depositor_treasury_seeds, &savings_vault_program_key)
+async fn list(cranker: &Cranker) -> Result<()> {
+    let program_id = cranker.config().program_id;
+    let store = CrankStore::open(&cranker.config().state_path)?;
+    let mut vaults = cranker.discover().await?;
+    for vault in &mut vaults {
+        cranker.apply_recorded_accrual(&store, vault);
+    }
+    let now = fetch_clock(cranker.rpc()).await?.unix_timestamp;
+    vaults.sort_by_key(|vault| cranker.next_due(vault));
+
+    println!("{} savings vault(s)", vaults.len());
+    for vault in &vaults {
+        let pdas = VaultPdas::derive(&program_id, &vault.wallet, &vault.mint);
+        let next_due = cranker.next_due(vault);
+        println!("{}", vault.savings_vault);
+        println!("  wallet:                      {}", vault.wallet);
+        println!("  mint:                        {}", vault.mint);
+        println!("  savings vault treasury:      {}", pdas.savings_vault_treasury);
+        println!("  interest depositor manager:  {}", pdas.interest_depositor_manager);
+        println!("  interest depositor treasury: {}", pdas.interest_depositor_treasury);
+        println!(
+            "  next due:                    {}{}",
+            format_timestamp(next_due),
+            if next_due <= now { " (overdue)" } else { "" }
+        );
+    }
+    Ok(())
 }
 
-async fn crank_accrue_interest(
-    client: &SavingsVaultClient,
-    cranker: &Keypair,
-    wallet: &Pubkey,
-    mint: &Pubkey,
-) -> Result<(), Error> {
-        let savings_vault_program_key: Pubkey  = Pubkey::from_str(SAVINGS_VAULT_PROGRAM_ID).unwrap();
-
-        let wallet = *wallet;
-        let mint = *mint;
-        let savings_vault: Pubkey = find_savings_vault_pda(&mint, &wallet).0;
-        let savings_vault_treasury: Pubkey = find_savings_vault_treasury_pda(&savings_vault).0;  
-        let interest_depositor_manager: Pubkey = find_interest_depositor_manager_pda(&mint).0;
-        let interest_depositor_treasury: Pubkey = find_interest_depositor_treasury_pda(&interest_depositor_manager).0;
-        let cranker_clone = cranker;
-        let program = client.program(savings_vault_program_key);
-        
-        let accrue_ix = program
-            .request()
-            .accounts(
-                accounts::AccrueInterest {
-                    mint,
-                    cranker: cranker_clone.pubkey(),
-                    wallet,
-                    savings_vault,
-                    savings_vault_treasury,
-                    interest_depositor_manager,
-                    interest_depositor_treasury,
-                    token_program: TOKEN_PROGRAM_ID,
-                    clock: sysvar::clock::ID,
-                });
-                            
-        let accrue_ix = accrue_ix.instructions()?;
-
-        let compute_ix = ComputeBudgetInstruction::set_compute_unit_limit(COMPUTE_UNITS);
-
-        let builder = program
-            .request()
-            .instruction(compute_ix)
-            .instruction(accrue_ix[0].clone())
-            .signer(cranker_clone);
-
-        let _sig = builder.send();
-
-        if let Err(_) | Ok(Response { value: None, .. }) = program
-            .rpc()
-            .get_account_with_commitment(&savings_vault, CommitmentConfig::processed())
-        {
-            let cluster_param = match get_cluster(program.rpc()).unwrap_or(Cluster::Mainnet) {
-                Cluster::Devnet => "?devnet",
-                _ => "",
-            };
-            return Err(anyhow!(
-                "Savings vault account {} does not exist on cluster {}",
-                savings_vault,
-                cluster_param
-            ));
-        }
-
-
+async fn status(cranker: &Cranker) -> Result<()> {
+    let config = cranker.config();
+    let links = cranker.explorer_links().await;
+    let lamports = cranker.rpc().get_balance(&cranker.pubkey()).await?;
+    let balance_status = match config.balance.status(lamports) {
+        BalanceStatus::Healthy => "",
+        BalanceStatus::Low => " (low)",
+        BalanceStatus::BelowFloor => " (below floor, cranking paused)",
+    };
+    println!("cranker {}: {} SOL{}", cranker.pubkey(), lamports_to_sol(lamports), balance_status);
+    println!("  {}", links.account(&cranker.pubkey()));
+
+    let vaults = cranker.discover().await?;
+    let mut checks: Vec<_> = cranker.check_solvency(&vaults).await?.into_values().collect();
+    checks.sort_by_key(|check| check.mint);
+    for check in &checks {
+        let vault_count = vaults.iter().filter(|vault| vault.mint == check.mint).count();
+        println!(
+            "mint {}: interest depositor treasury {} holds {}, the next accrual of {} vault(s) owes about {}{}",
+            check.mint,
+            check.interest_depositor_treasury,
+            check.balance,
+            vault_count,
+            check.owed,
+            if check.is_underfunded() { " (underfunded)" } else { "" }
+        );
+        println!("  {}", links.account(&check.interest_depositor_treasury));
+    }
     Ok(())
 }
 
-/// Hash for devnet cluster
-pub const DEVNET_HASH: &str = "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG";
+async fn run(cranker: Arc<Cranker>) -> Result<()> {
+    let store = Arc::new(CrankStore::open(&cranker.config().state_path)?);
 
-/// Hash for mainnet-beta cluster
-pub const MAINNET_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+    if let Some(addr) = cranker.config().metrics_addr {
+        let metrics = cranker.metrics().clone();
+        tokio::spawn(async move {
+            if let Err(err) = metrics::serve(addr, metrics).await {
+                error!(%addr, error = format!("{:#}", err), "metrics server stopped");
+            }
+        });
+    }
 
-pub fn get_cluster(rpc_client: RpcClient) -> Result<Cluster> {
-    let devnet_hash = Hash::from_str(DEVNET_HASH).unwrap();
-    let mainnet_hash = Hash::from_str(MAINNET_HASH).unwrap();
-    let genesis_hash = rpc_client.get_genesis_hash()?;
+    if cranker.config().dry_run {
+        let outcomes = cranker.run_cycle(&store).await?;
+        info!(vaults = outcomes.len(), "dry run finished");
+        return Ok(());
+    }
 
-    Ok(if genesis_hash == devnet_hash {
-        Cluster::Devnet
-    } else if genesis_hash == mainnet_hash {
-        Cluster::Mainnet
-    } else {
-        Cluster::Devnet
-    })
+    // Every outcome is already logged by the cranker.
+    Scheduler::new(cranker, store).run(|_, _| {}).await
 }
 
-#[tokio::main]
-async fn main() {
-    // let client = RpcClient::new(RPC_URL);
-    let cranker = read_keypair_file(KEYPAIR_PATH).unwrap();
-    let client = setup_client(&ClientConfig {
-        keypair: cranker,
-        rpc_url: RPC_URL.to_string(),
-    }).unwrap();
-    loop {
-        let last_execution_time = Utc::now();
-
-        let current_time = Utc::now();
-        let duration_since_last_execution = current_time.signed_duration_since(last_execution_time);
-        // get the wallets using the savings_vault protocol and mints supported from the database
-        let wallet: Pubkey = Pubkey::from_str("TUAXRFzyLeXmG9wPLaMXt66jUagfrWmL9oGq4rMwjAu").unwrap();
-        let mint: Pubkey = Pubkey::from_str("FmAFDKSPL61s8kQZCHwsZULA313pdHJ73PuBK4wePpNh").unwrap(); 
-        
-        if duration_since_last_execution.num_days() >= 30 {
-            let cranker = read_keypair_file(KEYPAIR_PATH).unwrap();
-            let _res = crank_accrue_interest(&client, &cranker, &wallet, &mint).await;
-        }
-        sleep(Duration::from_secs(60 * 60)); // check every hour
-    }
+fn format_timestamp(timestamp: UnixTimestamp) -> String {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .map_or_else(|| timestamp.to_string(), |time| time.to_rfc3339())
 }
@@ -0,0 +1,230 @@
+use {
+    std::{net::SocketAddr, sync::Arc, time::Duration},
+    prometheus::{
+        exponential_buckets, Encoder, Gauge, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec,
+        Opts, Registry, TextEncoder,
+    },
+    solana_sdk::{native_token::lamports_to_sol, pubkey::Pubkey},
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    },
+    anyhow::{anyhow, Result},
+    tracing::warn,
+    crate::outcome::CrankOutcome,
+};
+
+/// Largest HTTP request head read before answering a scrape.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// How long a scrape may take to send its request head before the connection is dropped.
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Prometheus metrics describing crank health, labeled by mint where they relate to vaults.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    accrue_attempts: IntCounterVec,
+    accrue_successes: IntCounterVec,
+    accrue_failures: IntCounterVec,
+    confirmation_seconds: HistogramVec,
+    compute_units: HistogramVec,
+    cranker_balance_sol: Gauge,
+    cranker_runway_days: Gauge,
+    vaults_discovered: IntGaugeVec,
+    vaults_due: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("crank_interest".to_string()), None)
+            .expect("static registry prefix is valid");
+        let accrue_attempts = IntCounterVec::new(
+            Opts::new("accrue_attempts_total", "AccrueInterest send attempts per vault"),
+            &["mint"],
+        )
+        .expect("static metric options are valid");
+        let accrue_successes = IntCounterVec::new(
+            Opts::new("accrue_successes_total", "Vaults that accrued interest"),
+            &["mint"],
+        )
+        .expect("static metric options are valid");
+        let accrue_failures = IntCounterVec::new(
+            Opts::new("accrue_failures_total", "Vault cranks that did not accrue, by error class"),
+            &["mint", "error_class"],
+        )
+        .expect("static metric options are valid");
+        let confirmation_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "confirmation_seconds",
+                "Time from sending an accrue transaction to reaching the commitment",
+            )
+            .buckets(exponential_buckets(0.25, 2.0, 10).expect("static buckets are valid")),
+            &["mint"],
+        )
+        .expect("static metric options are valid");
+        let compute_units = HistogramVec::new(
+            HistogramOpts::new("compute_units", "Simulated compute units consumed per accrue instruction")
+                .buckets(exponential_buckets(5_000.0, 2.0, 9).expect("static buckets are valid")),
+            &["mint"],
+        )
+        .expect("static metric options are valid");
+        let cranker_balance_sol = Gauge::new("cranker_balance_sol", "SOL balance of the cranker keypair")
+            .expect("static metric options are valid");
+        let cranker_runway_days = Gauge::new(
+            "cranker_runway_days",
+            "Days until the cranker balance runs out at its recent fee spend",
+        )
+        .expect("static metric options are valid");
+        let vaults_discovered = IntGaugeVec::new(
+            Opts::new("vaults_discovered", "Savings vaults found at the last refresh"),
+            &["mint"],
+        )
+        .expect("static metric options are valid");
+        let vaults_due = IntGaugeVec::new(
+            Opts::new("vaults_due", "Savings vaults whose due time had passed at the last refresh"),
+            &["mint"],
+        )
+        .expect("static metric options are valid");
+
+        for collector in [
+            Box::new(accrue_attempts.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(accrue_successes.clone()),
+            Box::new(accrue_failures.clone()),
+            Box::new(confirmation_seconds.clone()),
+            Box::new(compute_units.clone()),
+            Box::new(cranker_balance_sol.clone()),
+            Box::new(cranker_runway_days.clone()),
+            Box::new(vaults_discovered.clone()),
+            Box::new(vaults_due.clone()),
+        ] {
+            registry.register(collector).expect("every metric is registered once");
+        }
+
+        Self {
+            registry,
+            accrue_attempts,
+            accrue_successes,
+            accrue_failures,
+            confirmation_seconds,
+            compute_units,
+            cranker_balance_sol,
+            cranker_runway_days,
+            vaults_discovered,
+            vaults_due,
+        }
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    pub fn observe_attempt(&self, mint: &Pubkey) {
+        self.accrue_attempts.with_label_values(&[&mint.to_string()]).inc();
+    }
+
+    pub fn observe_outcome(&self, mint: &Pubkey, outcome: &CrankOutcome) {
+        let mint = mint.to_string();
+        match outcome {
+            CrankOutcome::Success { .. } => self.accrue_successes.with_label_values(&[&mint]).inc(),
+            CrankOutcome::Simulated { .. } => {}
+            CrankOutcome::Failed { error, .. } => self
+                .accrue_failures
+                .with_label_values(&[&mint, error.class()])
+                .inc(),
+        }
+    }
+
+    pub fn observe_confirmation(&self, mint: &Pubkey, elapsed: Duration) {
+        self.confirmation_seconds
+            .with_label_values(&[&mint.to_string()])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_compute_units(&self, mint: &Pubkey, units: u64) {
+        self.compute_units
+            .with_label_values(&[&mint.to_string()])
+            .observe(units as f64);
+    }
+
+    pub fn set_cranker_balance(&self, lamports: u64) {
+        self.cranker_balance_sol.set(lamports_to_sol(lamports));
+    }
+
+    pub fn set_cranker_runway(&self, runway: Duration) {
+        self.cranker_runway_days.set(runway.as_secs_f64() / (24.0 * 60.0 * 60.0));
+    }
+
+    /// Replaces the per-mint vault gauges so mints without vaults left stop being reported.
+    pub fn set_vault_counts<'a>(&self, counts: impl IntoIterator<Item = (&'a Pubkey, (i64, i64))>) {
+        self.vaults_discovered.reset();
+        self.vaults_due.reset();
+        for (mint, (discovered, due)) in counts {
+            let mint = mint.to_string();
+            self.vaults_discovered.with_label_values(&[&mint]).set(discovered);
+            self.vaults_due.with_label_values(&[&mint]).set(due);
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        // Encoding into a Vec cannot fail for the text format.
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut buffer);
+        buffer
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serves `GET /metrics` in the Prometheus text format on `addr` until the process exits.
+pub async fn serve(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_scrape(stream, &metrics).await {
+                warn!(error = %err, "failed to serve metrics scrape");
+            }
+        });
+    }
+}
+
+async fn handle_scrape(mut stream: TcpStream, metrics: &Metrics) -> Result<()> {
+    let request = tokio::time::timeout(REQUEST_READ_TIMEOUT, read_request_head(&mut stream))
+        .await
+        .map_err(|_| anyhow!("Timed out reading the scrape request"))??;
+
+    let (status, content_type, body) = if request.starts_with(b"GET /metrics ") {
+        ("200 OK", TextEncoder::new().format_type().to_string(), metrics.encode())
+    } else {
+        ("404 Not Found", "text/plain".to_string(), b"not found\n".to_vec())
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn read_request_head(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut request = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&chunk[..read]);
+    }
+    Ok(request)
+}
@@ -0,0 +1,257 @@
+use {
+    std::fmt,
+    solana_sdk::{instruction::InstructionError, signature::Signature, transaction::TransactionError},
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        rpc_request::{RpcError, RpcResponseErrorData},
+    },
+    spl_token::error::TokenError,
+    savings_vault::error::ErrorCode,
+    crate::error::CrankError,
+};
+
+/// savings_vault errors returned when the vault's interest period has not elapsed yet.
+pub const ALREADY_ACCRUED_ERRORS: &[ErrorCode] = &[ErrorCode::InterestAlreadyAccrued, ErrorCode::AccrualPeriodNotElapsed];
+
+/// savings_vault errors returned when the interest depositor treasury cannot pay out.
+pub const INSUFFICIENT_TREASURY_ERRORS: &[ErrorCode] =
+    &[ErrorCode::InsufficientInterestFunds, ErrorCode::InsufficientTreasuryBalance];
+
+fn is_one_of(code: u32, errors: &[ErrorCode]) -> bool {
+    errors.iter().any(|error| u32::from(*error) == code)
+}
+
+/// A custom program error raised by one of the instructions of an accrue transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProgramErrorInfo {
+    pub instruction_index: u8,
+    pub code: u32,
+    /// Anchor error name, e.g. `InterestAlreadyAccrued`, when the program logged one.
+    pub name: Option<String>,
+    pub message: Option<String>,
+}
+
+impl ProgramErrorInfo {
+    /// Extracts the custom error of `err`, naming it from the `AnchorError` log line if present.
+    pub fn from_transaction_error(err: &TransactionError, logs: &[String]) -> Option<Self> {
+        let (instruction_index, code) = match err {
+            TransactionError::InstructionError(index, InstructionError::Custom(code)) => (*index, *code),
+            _ => return None,
+        };
+        let mut info = Self {
+            instruction_index,
+            code,
+            name: None,
+            message: None,
+        };
+        if let Some((name, number, message)) = logs.iter().rev().find_map(|log| parse_anchor_error_log(log)) {
+            if number == code {
+                info.name = Some(name);
+                info.message = Some(message);
+            }
+        }
+        Some(info)
+    }
+
+    pub fn is_already_accrued(&self) -> bool {
+        is_one_of(self.code, ALREADY_ACCRUED_ERRORS)
+    }
+
+    pub fn is_insufficient_treasury(&self) -> bool {
+        is_one_of(self.code, INSUFFICIENT_TREASURY_ERRORS)
+            // The interest transfer CPI into the token program failing bubbles up as a bare code,
+            // which Anchor never logs an error name for.
+            || (self.name.is_none() && self.code == TokenError::InsufficientFunds as u32)
+    }
+}
+
+impl fmt::Display for ProgramErrorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.name, &self.message) {
+            (Some(name), Some(message)) => write!(
+                f,
+                "instruction {} failed with {} ({}): {}",
+                self.instruction_index, name, self.code, message
+            ),
+            _ => write!(
+                f,
+                "instruction {} failed with custom error {}",
+                self.instruction_index, self.code
+            ),
+        }
+    }
+}
+
+/// Parses `AnchorError ... Error Code: <name>. Error Number: <code>. Error Message: <message>.`
+fn parse_anchor_error_log(log: &str) -> Option<(String, u32, String)> {
+    if !log.contains("AnchorError") {
+        return None;
+    }
+    let rest = &log[log.find("Error Code: ")? + "Error Code: ".len()..];
+    let (name, rest) = rest.split_once(". Error Number: ")?;
+    let (number, message) = rest.split_once(". Error Message: ")?;
+    Some((
+        name.to_string(),
+        number.parse().ok()?,
+        message.trim_end_matches('.').to_string(),
+    ))
+}
+
+/// Result of a single `AccrueInterest` crank.
+#[derive(Clone, Debug)]
+pub enum CrankOutcome {
+    /// The transaction landed and reached the configured commitment.
+    Success { signature: Signature, slot: u64 },
+    /// Dry run: the transaction simulated successfully and was not sent.
+    Simulated { units_consumed: Option<u64>, compute_unit_limit: u32, compute_unit_price: u64 },
+    /// The vault was not cranked, with the signature of the transaction if one was sent.
+    Failed { signature: Option<Signature>, error: CrankError },
+}
+
+impl CrankOutcome {
+    pub fn failed(signature: Option<Signature>, error: CrankError) -> Self {
+        Self::Failed { signature, error }
+    }
+
+    /// `Failed` outcome of `signature` holding `CrankError::from_transaction_error` of `error`.
+    pub fn from_transaction_error(signature: Option<Signature>, error: TransactionError, logs: &[String]) -> Self {
+        Self::failed(signature, CrankError::from_transaction_error(error, logs))
+    }
+
+    /// Classifies an error returned while sending, including preflight simulation failures.
+    pub fn from_client_error(err: &ClientError) -> Self {
+        if let ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+            ..
+        }) = err.kind()
+        {
+            if let Some(error) = result.err.clone() {
+                return Self::from_transaction_error(None, error, result.logs.as_deref().unwrap_or_default());
+            }
+        }
+        match err.get_transaction_error() {
+            Some(error) => Self::from_transaction_error(None, error, &[]),
+            None => Self::rpc_failure(err),
+        }
+    }
+
+    pub fn rpc_failure(err: &ClientError) -> Self {
+        Self::failed(None, CrankError::from_client_error(err))
+    }
+
+    pub fn signature(&self) -> Option<Signature> {
+        match self {
+            Self::Success { signature, .. } => Some(*signature),
+            Self::Failed { signature, .. } => *signature,
+            Self::Simulated { .. } => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&CrankError> {
+        match self {
+            Self::Failed { error, .. } => Some(error),
+            Self::Success { .. } | Self::Simulated { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for CrankOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Success { signature, slot } => write!(f, "accrued in {} at slot {}", signature, slot),
+            Self::Simulated {
+                units_consumed,
+                compute_unit_limit,
+                compute_unit_price,
+            } => write!(
+                f,
+                "would accrue using {} of {} compute units at {} micro-lamports/CU",
+                units_consumed.map_or_else(|| "unknown".to_string(), |units| units.to_string()),
+                compute_unit_limit,
+                compute_unit_price
+            ),
+            Self::Failed {
+                signature: Some(signature),
+                error,
+            } => write!(f, "{} in {}", error, signature),
+            Self::Failed { signature: None, error } => write!(f, "{}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchor_log(name: &str, code: u32) -> String {
+        format!(
+            "Program log: AnchorError thrown in programs/savings_vault/src/lib.rs:42. Error Code: {}. \
+             Error Number: {}. Error Message: Interest was already accrued for this period.",
+            name, code
+        )
+    }
+
+    fn custom(index: u8, code: u32) -> TransactionError {
+        TransactionError::InstructionError(index, InstructionError::Custom(code))
+    }
+
+    #[test]
+    fn parses_anchor_error_logs() {
+        assert_eq!(
+            parse_anchor_error_log(&anchor_log("InterestAlreadyAccrued", 6003)),
+            Some((
+                "InterestAlreadyAccrued".to_string(),
+                6003,
+                "Interest was already accrued for this period".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_anchor_error_log("Program log: AnchorError occurred. Error Code: Foo. Error Number: x. Error Message: y."),
+            None
+        );
+        assert_eq!(parse_anchor_error_log("Program log: Instruction: AccrueInterest"), None);
+    }
+
+    #[test]
+    fn names_errors_only_from_a_matching_log() {
+        let code = u32::from(ErrorCode::InterestAlreadyAccrued);
+        let logs = vec![anchor_log("InterestAlreadyAccrued", code)];
+        let info = ProgramErrorInfo::from_transaction_error(&custom(2, code), &logs).unwrap();
+        assert_eq!(info.instruction_index, 2);
+        assert_eq!(info.name.as_deref(), Some("InterestAlreadyAccrued"));
+
+        let info = ProgramErrorInfo::from_transaction_error(&custom(2, code + 1), &logs).unwrap();
+        assert_eq!(info.name, None);
+
+        assert_eq!(
+            ProgramErrorInfo::from_transaction_error(&TransactionError::BlockhashNotFound, &logs),
+            None
+        );
+    }
+
+    #[test]
+    fn matches_savings_vault_error_codes() {
+        for error in ALREADY_ACCRUED_ERRORS {
+            let info = ProgramErrorInfo::from_transaction_error(&custom(2, u32::from(*error)), &[]).unwrap();
+            assert!(info.is_already_accrued(), "{}", info);
+            assert!(!info.is_insufficient_treasury(), "{}", info);
+        }
+        for error in INSUFFICIENT_TREASURY_ERRORS {
+            let info = ProgramErrorInfo::from_transaction_error(&custom(2, u32::from(*error)), &[]).unwrap();
+            assert!(info.is_insufficient_treasury(), "{}", info);
+            assert!(!info.is_already_accrued(), "{}", info);
+        }
+    }
+
+    #[test]
+    fn bare_token_insufficient_funds_is_an_insufficient_treasury() {
+        let code = TokenError::InsufficientFunds as u32;
+        let info = ProgramErrorInfo::from_transaction_error(&custom(2, code), &[]).unwrap();
+        assert!(info.is_insufficient_treasury());
+
+        // Only the token program fails without Anchor logging an error name.
+        let logs = vec![anchor_log("InvalidOwner", code)];
+        let info = ProgramErrorInfo::from_transaction_error(&custom(2, code), &logs).unwrap();
+        assert!(!info.is_insufficient_treasury());
+    }
+}
@@ -0,0 +1,57 @@
+use solana_sdk::pubkey::Pubkey;
+
+pub const SEED_SAVINGS_VAULT: &[u8] = b"savings_vault";
+pub const SEED_SAVINGS_VAULT_TREASURY: &[u8] = b"savings_vault-treasury";
+pub const SEED_INTEREST_DEPOSITOR_MANAGER: &[u8] = b"interest_depositor_manager";
+pub const SEED_INTEREST_DEPOSITOR_TREASURY: &[u8] = b"interest_depositor_treasury";
+
+pub fn find_savings_vault_pda(program_id: &Pubkey, mint: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    let savings_vault_seeds = &[SEED_SAVINGS_VAULT, mint.as_ref(), wallet.as_ref()];
+
+    Pubkey::find_program_address(savings_vault_seeds, program_id)
+}
+
+
+pub fn find_savings_vault_treasury_pda(program_id: &Pubkey, savings_vault: &Pubkey) -> (Pubkey, u8) {
+    let savings_vault_treasury_seeds = &[SEED_SAVINGS_VAULT_TREASURY, savings_vault.as_ref()];
+
+    Pubkey::find_program_address(savings_vault_treasury_seeds, program_id)
+}
+
+pub fn find_interest_depositor_manager_pda(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    let interest_depositor_manager_seeds = &[SEED_INTEREST_DEPOSITOR_MANAGER, mint.as_ref()];
+
+    Pubkey::find_program_address(interest_depositor_manager_seeds, program_id)
+}
+
+pub fn find_interest_depositor_treasury_pda(program_id: &Pubkey, interest_depositor_manager: &Pubkey) -> (Pubkey, u8) {
+    let interest_depositor_treasury_seeds = &[SEED_INTEREST_DEPOSITOR_TREASURY, interest_depositor_manager.as_ref()];
+
+    Pubkey::find_program_address(interest_depositor_treasury_seeds, program_id)
+}
+
+/// Every PDA the savings vault program derives for one wallet and mint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VaultPdas {
+    pub savings_vault: Pubkey,
+    pub savings_vault_treasury: Pubkey,
+    pub interest_depositor_manager: Pubkey,
+    pub interest_depositor_treasury: Pubkey,
+}
+
+impl VaultPdas {
+    pub fn derive(program_id: &Pubkey, wallet: &Pubkey, mint: &Pubkey) -> Self {
+        let savings_vault = find_savings_vault_pda(program_id, mint, wallet).0;
+        let savings_vault_treasury = find_savings_vault_treasury_pda(program_id, &savings_vault).0;
+        let interest_depositor_manager = find_interest_depositor_manager_pda(program_id, mint).0;
+        let interest_depositor_treasury =
+            find_interest_depositor_treasury_pda(program_id, &interest_depositor_manager).0;
+
+        Self {
+            savings_vault,
+            savings_vault_treasury,
+            interest_depositor_manager,
+            interest_depositor_treasury,
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use {
+    std::{
+        collections::{HashMap, HashSet},
+        fmt,
+    },
+    solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey},
+    solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient, rpc_request::MAX_MULTIPLE_ACCOUNTS},
+    spl_token::{state::Account as TokenAccount, ID as TOKEN_PROGRAM_ID},
+    anchor_client::anchor_lang::Discriminator,
+    savings_vault::state::{InterestDepositorManager, SavingsVault},
+    crate::{client::DetectedCluster, discovery::DiscoveredVault, error::CrankError, pda::VaultPdas},
+};
+
+/// Length of the Anchor account discriminator every savings_vault account starts with.
+const DISCRIMINATOR_LEN: usize = 8;
+
+/// Why an account `AccrueInterest` reads would make the instruction fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountProblem {
+    Missing,
+    /// Owned by this program instead of the expected one.
+    WrongOwner(Pubkey),
+    /// Owned by the savings_vault program but holding another account type.
+    WrongDiscriminator,
+    /// Owned by the token program but not an initialized token account.
+    NotTokenAccount,
+    /// A token account of this mint instead of the vault's.
+    WrongMint(Pubkey),
+    /// Data that does not deserialize as the expected account type.
+    Malformed,
+}
+
+impl fmt::Display for AccountProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => f.write_str("does not exist"),
+            Self::WrongOwner(owner) => write!(f, "is owned by {}", owner),
+            Self::WrongDiscriminator => f.write_str("has the wrong account discriminator"),
+            Self::NotTokenAccount => f.write_str("is not an initialized token account"),
+            Self::WrongMint(mint) => write!(f, "holds mint {}", mint),
+            Self::Malformed => f.write_str("does not deserialize"),
+        }
+    }
+}
+
+/// Reads every account the `AccrueInterest` instructions of `vaults` read, keyed by address and
+/// leaving out accounts that do not exist.
+///
+/// The savings vault, both treasuries and the interest depositor manager of all vaults are read
+/// with a single `getMultipleAccounts` call, split only when they exceed its account limit.
+pub async fn fetch_vault_accounts(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    vaults: &[DiscoveredVault],
+) -> Result<HashMap<Pubkey, Account>, ClientError> {
+    // Vaults of one mint share their interest depositor manager and treasury.
+    let mut keys: Vec<Pubkey> = Vec::with_capacity(vaults.len() * 4);
+    let mut seen: HashSet<Pubkey> = HashSet::with_capacity(vaults.len() * 4);
+    for vault in vaults {
+        let pdas = VaultPdas::derive(program_id, &vault.wallet, &vault.mint);
+        for key in [
+            pdas.savings_vault,
+            pdas.savings_vault_treasury,
+            pdas.interest_depositor_manager,
+            pdas.interest_depositor_treasury,
+        ] {
+            if seen.insert(key) {
+                keys.push(key);
+            }
+        }
+    }
+
+    let mut accounts: HashMap<Pubkey, Account> = HashMap::with_capacity(keys.len());
+    for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let fetched = rpc.get_multiple_accounts(chunk).await?;
+        accounts.extend(
+            chunk
+                .iter()
+                .zip(fetched)
+                .filter_map(|(key, account)| account.map(|account| (*key, account))),
+        );
+    }
+    Ok(accounts)
+}
+
+/// Checks the accounts of `vaults`, as read by `fetch_vault_accounts`, before anything is sent,
+/// returning one result per vault.
+pub fn validate_vaults(
+    program_id: &Pubkey,
+    vaults: &[DiscoveredVault],
+    accounts: &HashMap<Pubkey, Account>,
+    cluster: Option<DetectedCluster>,
+) -> Vec<Result<(), CrankError>> {
+    vaults
+        .iter()
+        .map(|vault| {
+            let pdas = VaultPdas::derive(program_id, &vault.wallet, &vault.mint);
+            validate_vault(program_id, vault, &pdas, accounts, cluster)
+        })
+        .collect()
+}
+
+/// Checks the owners and discriminators of the savings vault and interest depositor manager, and
+/// that both treasuries are token accounts of the vault's mint.
+pub fn validate_vault(
+    program_id: &Pubkey,
+    vault: &DiscoveredVault,
+    pdas: &VaultPdas,
+    accounts: &HashMap<Pubkey, Account>,
+    cluster: Option<DetectedCluster>,
+) -> Result<(), CrankError> {
+    if !accounts.contains_key(&pdas.savings_vault) {
+        return Err(CrankError::VaultNotFound {
+            savings_vault: pdas.savings_vault,
+            cluster,
+        });
+    }
+
+    let invalid = |name, account, problem| CrankError::InvalidAccount { name, account, problem };
+    let program_accounts = [
+        ("savings vault", pdas.savings_vault, SavingsVault::discriminator()),
+        (
+            "interest depositor manager",
+            pdas.interest_depositor_manager,
+            InterestDepositorManager::discriminator(),
+        ),
+    ];
+    for (name, key, discriminator) in program_accounts {
+        check_program_account(accounts.get(&key), program_id, &discriminator)
+            .map_err(|problem| invalid(name, key, problem))?;
+    }
+
+    let token_accounts = [
+        ("savings vault treasury", pdas.savings_vault_treasury),
+        ("interest depositor treasury", pdas.interest_depositor_treasury),
+    ];
+    for (name, key) in token_accounts {
+        check_token_account(accounts.get(&key), &vault.mint).map_err(|problem| invalid(name, key, problem))?;
+    }
+
+    Ok(())
+}
+
+fn check_program_account(
+    account: Option<&Account>,
+    program_id: &Pubkey,
+    discriminator: &[u8; DISCRIMINATOR_LEN],
+) -> Result<(), AccountProblem> {
+    let account = account.ok_or(AccountProblem::Missing)?;
+    if account.owner != *program_id {
+        return Err(AccountProblem::WrongOwner(account.owner));
+    }
+    if account.data.get(..DISCRIMINATOR_LEN) != Some(discriminator.as_slice()) {
+        return Err(AccountProblem::WrongDiscriminator);
+    }
+    Ok(())
+}
+
+fn check_token_account(account: Option<&Account>, mint: &Pubkey) -> Result<(), AccountProblem> {
+    let account = account.ok_or(AccountProblem::Missing)?;
+    if account.owner != TOKEN_PROGRAM_ID {
+        return Err(AccountProblem::WrongOwner(account.owner));
+    }
+    let token_account = TokenAccount::unpack(&account.data).map_err(|_| AccountProblem::NotTokenAccount)?;
+    if token_account.mint != *mint {
+        return Err(AccountProblem::WrongMint(token_account.mint));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        spl_token::state::AccountState,
+        crate::vault_state::VaultSchedule,
+    };
+
+    struct Fixture {
+        program_id: Pubkey,
+        vault: DiscoveredVault,
+        pdas: VaultPdas,
+        accounts: HashMap<Pubkey, Account>,
+    }
+
+    impl Fixture {
+        /// A vault whose four accounts are all valid.
+        fn new() -> Self {
+            let program_id = Pubkey::new_unique();
+            let mut vault = DiscoveredVault {
+                savings_vault: Pubkey::default(),
+                wallet: Pubkey::new_unique(),
+                mint: Pubkey::new_unique(),
+                schedule: VaultSchedule {
+                    last_accrued_at: 0,
+                    interest_period: 0,
+                },
+                interest_rate_bps: 100,
+            };
+            let pdas = VaultPdas::derive(&program_id, &vault.wallet, &vault.mint);
+            vault.savings_vault = pdas.savings_vault;
+            let mut fixture = Self {
+                program_id,
+                vault,
+                pdas,
+                accounts: HashMap::new(),
+            };
+            fixture.set(pdas.savings_vault, program_account(&program_id, &SavingsVault::discriminator()));
+            fixture.set(
+                pdas.interest_depositor_manager,
+                program_account(&program_id, &InterestDepositorManager::discriminator()),
+            );
+            fixture.set(pdas.savings_vault_treasury, token_account(&vault.mint));
+            fixture.set(pdas.interest_depositor_treasury, token_account(&vault.mint));
+            fixture
+        }
+
+        fn set(&mut self, key: Pubkey, account: Account) {
+            self.accounts.insert(key, account);
+        }
+
+        fn validate(&self) -> Result<(), CrankError> {
+            validate_vault(&self.program_id, &self.vault, &self.pdas, &self.accounts, None)
+        }
+
+        fn problem(&self) -> (&'static str, Pubkey, AccountProblem) {
+            match self.validate() {
+                Err(CrankError::InvalidAccount { name, account, problem }) => (name, account, problem),
+                result => panic!("expected an invalid account, got {:?}", result),
+            }
+        }
+    }
+
+    fn program_account(owner: &Pubkey, discriminator: &[u8; DISCRIMINATOR_LEN]) -> Account {
+        let mut data = discriminator.to_vec();
+        data.resize(128, 0);
+        Account {
+            lamports: 1,
+            data,
+            owner: *owner,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn token_account(mint: &Pubkey) -> Account {
+        let mut data = vec![0; TokenAccount::LEN];
+        let token_account = TokenAccount {
+            mint: *mint,
+            owner: Pubkey::new_unique(),
+            amount: 1_000,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        };
+        TokenAccount::pack(token_account, &mut data).unwrap();
+        Account {
+            lamports: 1,
+            data,
+            owner: TOKEN_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn accepts_valid_accounts() {
+        assert_eq!(Fixture::new().validate(), Ok(()));
+    }
+
+    #[test]
+    fn reports_a_missing_savings_vault_as_not_found() {
+        let mut fixture = Fixture::new();
+        fixture.accounts.remove(&fixture.pdas.savings_vault);
+        assert_eq!(
+            fixture.validate(),
+            Err(CrankError::VaultNotFound {
+                savings_vault: fixture.pdas.savings_vault,
+                cluster: None,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_a_missing_account() {
+        let mut fixture = Fixture::new();
+        fixture.accounts.remove(&fixture.pdas.interest_depositor_treasury);
+        assert_eq!(
+            fixture.problem(),
+            (
+                "interest depositor treasury",
+                fixture.pdas.interest_depositor_treasury,
+                AccountProblem::Missing
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_the_wrong_owner() {
+        let mut fixture = Fixture::new();
+        let owner = Pubkey::new_unique();
+        fixture.set(fixture.pdas.savings_vault, program_account(&owner, &SavingsVault::discriminator()));
+        assert_eq!(
+            fixture.problem(),
+            ("savings vault", fixture.pdas.savings_vault, AccountProblem::WrongOwner(owner))
+        );
+    }
+
+    #[test]
+    fn rejects_the_wrong_discriminator() {
+        let mut fixture = Fixture::new();
+        let program_id = fixture.program_id;
+        fixture.set(
+            fixture.pdas.interest_depositor_manager,
+            program_account(&program_id, &SavingsVault::discriminator()),
+        );
+        assert_eq!(
+            fixture.problem(),
+            (
+                "interest depositor manager",
+                fixture.pdas.interest_depositor_manager,
+                AccountProblem::WrongDiscriminator
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_an_account_that_is_not_a_token_account() {
+        let mut fixture = Fixture::new();
+        let mut account = token_account(&fixture.vault.mint);
+        account.data.truncate(10);
+        fixture.set(fixture.pdas.savings_vault_treasury, account);
+        assert_eq!(
+            fixture.problem(),
+            (
+                "savings vault treasury",
+                fixture.pdas.savings_vault_treasury,
+                AccountProblem::NotTokenAccount
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_a_token_account_of_another_mint() {
+        let mut fixture = Fixture::new();
+        let mint = Pubkey::new_unique();
+        fixture.set(fixture.pdas.interest_depositor_treasury, token_account(&mint));
+        assert_eq!(
+            fixture.problem(),
+            (
+                "interest depositor treasury",
+                fixture.pdas.interest_depositor_treasury,
+                AccountProblem::WrongMint(mint)
+            )
+        );
+    }
+}
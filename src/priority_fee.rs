@@ -0,0 +1,106 @@
+use {
+    solana_sdk::{instruction::Instruction, pubkey::Pubkey},
+    solana_client::{client_error::Result as ClientResult, nonblocking::rpc_client::RpcClient},
+};
+
+/// Most accounts `getRecentPrioritizationFees` accepts in a single request.
+pub const MAX_PRIORITIZATION_FEE_ACCOUNTS: usize = 128;
+
+/// Price retries escalate from when the first attempt bid nothing, in micro-lamports per compute
+/// unit, since multiplying zero would never raise the bid.
+pub const MIN_ESCALATION_BASE_MICRO_LAMPORTS: u64 = 1;
+
+/// How the compute unit price of accrue transactions is chosen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriorityFeePolicy {
+    /// Percentile (0 to 100) of the recent prioritization fees paid for the written accounts.
+    pub percentile: u8,
+    /// Lowest price paid, in micro-lamports per compute unit.
+    pub min_micro_lamports: u64,
+    /// Highest price paid, in micro-lamports per compute unit, escalation included.
+    pub max_micro_lamports: u64,
+    /// Multiplier applied to the price on every retry.
+    pub escalation: f64,
+}
+
+impl PriorityFeePolicy {
+    /// Price for `attempt` (starting at 1) given the `recent_fee` observed at the configured percentile.
+    pub fn price_for_attempt(&self, recent_fee: u64, attempt: u32) -> u64 {
+        let base = recent_fee.max(self.min_micro_lamports);
+        if attempt <= 1 {
+            return base.min(self.max_micro_lamports);
+        }
+        let base = base.max(MIN_ESCALATION_BASE_MICRO_LAMPORTS) as f64;
+        let escalated = base * self.escalation.powi(attempt.saturating_sub(1) as i32);
+        (escalated.ceil() as u64).clamp(self.min_micro_lamports, self.max_micro_lamports)
+    }
+
+    /// Recent prioritization fee at the configured percentile for transactions writing `accounts`.
+    pub async fn recent_fee(&self, rpc: &RpcClient, accounts: &[Pubkey]) -> ClientResult<u64> {
+        let accounts = &accounts[..accounts.len().min(MAX_PRIORITIZATION_FEE_ACCOUNTS)];
+        let mut fees: Vec<u64> = rpc
+            .get_recent_prioritization_fees(accounts)
+            .await?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect();
+        if fees.is_empty() {
+            return Ok(0);
+        }
+        fees.sort_unstable();
+        let index = (fees.len() - 1) * usize::from(self.percentile.min(100)) / 100;
+        Ok(fees[index])
+    }
+}
+
+/// Every account `instructions` write to, except `payer` whose fee market is not contended.
+pub fn writable_accounts(instructions: &[Instruction], payer: &Pubkey) -> Vec<Pubkey> {
+    let mut accounts: Vec<Pubkey> = instructions
+        .iter()
+        .flat_map(|instruction| instruction.accounts.iter())
+        .filter(|meta| meta.is_writable && meta.pubkey != *payer)
+        .map(|meta| meta.pubkey)
+        .collect();
+    accounts.sort_unstable();
+    accounts.dedup();
+    accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(min_micro_lamports: u64) -> PriorityFeePolicy {
+        PriorityFeePolicy {
+            percentile: 75,
+            min_micro_lamports,
+            max_micro_lamports: 1_000_000,
+            escalation: 2.0,
+        }
+    }
+
+    #[test]
+    fn escalates_from_the_recent_fee() {
+        let policy = policy(0);
+        assert_eq!(policy.price_for_attempt(1_000, 1), 1_000);
+        assert_eq!(policy.price_for_attempt(1_000, 2), 2_000);
+        assert_eq!(policy.price_for_attempt(1_000, 3), 4_000);
+    }
+
+    #[test]
+    fn escalates_without_a_recent_fee_or_floor() {
+        let policy = policy(0);
+        assert_eq!(policy.price_for_attempt(0, 1), 0);
+        let prices: Vec<u64> = (2..=5).map(|attempt| policy.price_for_attempt(0, attempt)).collect();
+        assert!(prices[0] > 0, "{:?}", prices);
+        assert!(prices.windows(2).all(|pair| pair[1] > pair[0]), "{:?}", prices);
+    }
+
+    #[test]
+    fn stays_within_the_floor_and_cap() {
+        let policy = policy(500);
+        assert_eq!(policy.price_for_attempt(0, 1), 500);
+        assert_eq!(policy.price_for_attempt(0, 2), 1_000);
+        assert_eq!(policy.price_for_attempt(900_000, 3), 1_000_000);
+    }
+}
@@ -0,0 +1,75 @@
+use {
+    std::time::Duration,
+    rand::Rng,
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+        rpc_request::RpcError,
+    },
+    solana_sdk::transaction::TransactionError,
+    crate::{error::CrankError, outcome::CrankOutcome},
+};
+
+/// HTTP status RPC providers answer with when rate limiting.
+pub const HTTP_TOO_MANY_REQUESTS: u16 = 429;
+
+/// How many times, and how far apart, a failed crank is attempted again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Fraction in `0.0..=1.0` by which each delay is randomly shortened or stretched.
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// Delay to wait after the failed `attempt` (starting at 1) before trying again.
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .saturating_mul(1u32 << exponent)
+            .min(self.max_delay);
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        let factor = rand::thread_rng().gen_range(1.0 - self.jitter..=1.0 + self.jitter);
+        delay.mul_f64(factor).min(self.max_delay)
+    }
+}
+
+/// Whether trying again with a fresh blockhash can change `outcome`, see `CrankError::is_retryable`.
+pub fn is_retryable(outcome: &CrankOutcome) -> bool {
+    outcome.error().map_or(false, CrankError::is_retryable)
+}
+
+pub fn is_retryable_transaction_error(error: &TransactionError) -> bool {
+    matches!(
+        error,
+        TransactionError::BlockhashNotFound
+            | TransactionError::WouldExceedMaxBlockCostLimit
+            | TransactionError::WouldExceedMaxAccountCostLimit
+            | TransactionError::WouldExceedAccountDataBlockLimit
+            | TransactionError::ClusterMaintenance
+    )
+}
+
+/// Transport failures, rate limiting and unhealthy or lagging nodes are transient.
+pub fn is_retryable_client_error(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Io(_) => true,
+        ClientErrorKind::Reqwest(err) => {
+            err.is_timeout()
+                || err.is_connect()
+                || err.status().map(|status| status.as_u16()) == Some(HTTP_TOO_MANY_REQUESTS)
+                || err.status().map_or(false, |status| status.is_server_error())
+        }
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => {
+            *code == JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+        }
+        ClientErrorKind::TransactionError(error) => is_retryable_transaction_error(error),
+        _ => false,
+    }
+}
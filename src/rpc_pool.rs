@@ -0,0 +1,257 @@
+use {
+    std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        time::{Duration, Instant},
+    },
+    solana_client::nonblocking::rpc_client::RpcClient,
+    anyhow::Result,
+    tracing::{info, warn},
+    crate::client::{redact_url, redact_urls, ClientConfig},
+};
+
+/// Weight of the newest sample in the latency and error rate moving averages.
+const EWMA_WEIGHT: f64 = 0.2;
+
+/// Error rate above which an endpoint is failed over from even if it keeps up with the cluster.
+pub const MAX_ERROR_RATE: f64 = 0.5;
+
+/// Rolling health of a single RPC endpoint.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EndpointHealth {
+    /// Slot reported by the last successful health check.
+    pub slot: u64,
+    /// Slots behind the most advanced endpoint at the last health check, `u64::MAX` when that
+    /// check failed.
+    pub slot_lag: u64,
+    /// Moving average of health check latency, zero until a check succeeds.
+    pub latency: Duration,
+    /// Moving average of failed requests, from 0.0 to 1.0.
+    pub error_rate: f64,
+}
+
+impl EndpointHealth {
+    fn record(&mut self, ok: bool) {
+        let sample = if ok { 0.0 } else { 1.0 };
+        self.error_rate += EWMA_WEIGHT * (sample - self.error_rate);
+    }
+
+    /// Records a health check that returned `slot`, or failed when `None`, against the
+    /// `highest_slot` any endpoint returned.
+    fn record_check(&mut self, slot: Option<u64>, highest_slot: u64, latency: Duration) {
+        self.record(slot.is_some());
+        match slot {
+            Some(slot) => {
+                self.slot = slot;
+                self.slot_lag = highest_slot.saturating_sub(slot);
+                self.latency = if self.latency.is_zero() {
+                    latency
+                } else {
+                    self.latency.mul_f64(1.0 - EWMA_WEIGHT) + latency.mul_f64(EWMA_WEIGHT)
+                };
+            }
+            // An endpoint that cannot report its slot is not failed over to until it does again.
+            None => self.slot_lag = u64::MAX,
+        }
+    }
+
+    fn is_healthy(&self, max_slot_lag: u64) -> bool {
+        self.slot_lag <= max_slot_lag && self.error_rate <= MAX_ERROR_RATE
+    }
+}
+
+/// One RPC endpoint of a pool.
+pub struct RpcEndpoint {
+    pub url: String,
+    pub ws_url: String,
+    client: RpcClient,
+    health: Mutex<EndpointHealth>,
+}
+
+impl RpcEndpoint {
+    pub fn client(&self) -> &RpcClient {
+        &self.client
+    }
+
+    pub fn health(&self) -> EndpointHealth {
+        self.health.lock().map(|health| *health).unwrap_or_default()
+    }
+}
+
+/// RPC endpoints scored by slot lag, latency and error rate. Reads and sends go to the current
+/// endpoint, which is replaced by the healthiest one whenever it errors too often or falls
+/// behind the cluster.
+pub struct RpcPool {
+    endpoints: Vec<RpcEndpoint>,
+    current: AtomicUsize,
+    max_slot_lag: u64,
+}
+
+impl RpcPool {
+    /// Creates a pool over a client for each of `endpoints`, starting with the first one.
+    pub fn new(endpoints: &[ClientConfig], max_slot_lag: u64) -> Result<Self> {
+        assert!(!endpoints.is_empty(), "an RPC pool needs at least one endpoint");
+        let endpoints = endpoints
+            .iter()
+            .map(|config| {
+                Ok(RpcEndpoint {
+                    url: config.rpc_url.clone(),
+                    ws_url: config.ws_url(),
+                    client: config.rpc_client()?,
+                    health: Mutex::new(EndpointHealth::default()),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            endpoints,
+            current: AtomicUsize::new(0),
+            max_slot_lag,
+        })
+    }
+
+    pub fn endpoints(&self) -> &[RpcEndpoint] {
+        &self.endpoints
+    }
+
+    pub fn current(&self) -> &RpcEndpoint {
+        &self.endpoints[self.current.load(Ordering::Relaxed)]
+    }
+
+    /// Client of the current endpoint.
+    pub fn client(&self) -> &RpcClient {
+        &self.current().client
+    }
+
+    /// Counts a request to the current endpoint, failing over when it errors too often.
+    pub fn record(&self, ok: bool) {
+        let index = self.current.load(Ordering::Relaxed);
+        let healthy = match self.endpoints[index].health.lock() {
+            Ok(mut health) => {
+                health.record(ok);
+                health.is_healthy(self.max_slot_lag)
+            }
+            Err(_) => return,
+        };
+        if !healthy {
+            self.select();
+        }
+    }
+
+    /// Queries every endpoint's slot, updates slot lag, latency and error rate, and switches to
+    /// the healthiest endpoint.
+    pub async fn check_health(&self) {
+        let checks = self.endpoints.iter().map(|endpoint| async move {
+            let started = Instant::now();
+            let slot = endpoint.client.get_slot().await;
+            (slot, started.elapsed())
+        });
+        let results = futures::future::join_all(checks).await;
+        let highest_slot = results
+            .iter()
+            .filter_map(|(slot, _)| slot.as_ref().ok())
+            .copied()
+            .max()
+            .unwrap_or_default();
+
+        for (endpoint, (slot, latency)) in self.endpoints.iter().zip(results) {
+            let mut health = match endpoint.health.lock() {
+                Ok(health) => health,
+                Err(_) => continue,
+            };
+            if let Err(err) = &slot {
+                warn!(
+                    rpc_url = %redact_url(&endpoint.url),
+                    error = %redact_urls(&err.to_string()),
+                    "RPC health check failed"
+                );
+            }
+            health.record_check(slot.ok(), highest_slot, latency);
+        }
+        self.select();
+    }
+
+    /// Makes the healthiest endpoint current: endpoints within the slot lag and error rate limits
+    /// first, then those whose latency was measured, then by error rate weighted latency.
+    fn select(&self) {
+        let scores: Vec<(bool, bool, f64)> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let health = endpoint.health();
+                let latency = health.latency.as_secs_f64();
+                (
+                    health.is_healthy(self.max_slot_lag),
+                    !health.latency.is_zero(),
+                    latency * (1.0 + health.error_rate * 10.0),
+                )
+            })
+            .collect();
+        let best = (0..scores.len())
+            .min_by(|&a, &b| {
+                let (a_healthy, a_measured, a_score) = scores[a];
+                let (b_healthy, b_measured, b_score) = scores[b];
+                b_healthy
+                    .cmp(&a_healthy)
+                    .then(b_measured.cmp(&a_measured))
+                    .then(a_score.total_cmp(&b_score))
+            })
+            .unwrap_or(0);
+
+        let previous = self.current.swap(best, Ordering::Relaxed);
+        if previous != best {
+            info!(
+                from = %redact_url(&self.endpoints[previous].url),
+                to = %redact_url(&self.endpoints[best].url),
+                "failing over to a healthier RPC endpoint"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(urls: &[&str]) -> RpcPool {
+        let configs: Vec<ClientConfig> = urls.iter().map(|url| ClientConfig::new(url.to_string())).collect();
+        RpcPool::new(&configs, 50).unwrap()
+    }
+
+    fn set_health(pool: &RpcPool, index: usize, update: impl FnOnce(&mut EndpointHealth)) {
+        update(&mut pool.endpoints[index].health.lock().unwrap());
+    }
+
+    #[test]
+    fn failed_check_makes_endpoint_unhealthy() {
+        let mut health = EndpointHealth::default();
+        health.record_check(None, 100, Duration::from_millis(5));
+        assert_eq!(health.slot_lag, u64::MAX);
+        assert!(health.latency.is_zero());
+        assert!(!health.is_healthy(50));
+
+        health.record_check(Some(90), 100, Duration::from_millis(5));
+        assert_eq!(health.slot_lag, 10);
+        assert_eq!(health.latency, Duration::from_millis(5));
+    }
+
+    #[test]
+    fn never_answering_endpoint_is_not_selected() {
+        let pool = pool(&["http://127.0.0.1:1", "http://127.0.0.1:2", "http://127.0.0.1:3"]);
+        set_health(&pool, 0, |health| health.record_check(Some(100), 100, Duration::from_millis(80)));
+        set_health(&pool, 1, |health| health.record_check(None, 100, Duration::from_millis(1)));
+        set_health(&pool, 2, |health| health.record_check(Some(100), 100, Duration::from_millis(40)));
+        pool.select();
+        assert_eq!(pool.current().url, "http://127.0.0.1:3");
+    }
+
+    #[test]
+    fn measured_endpoints_rank_above_unmeasured_ones() {
+        let pool = pool(&["http://127.0.0.1:1", "http://127.0.0.1:2"]);
+        set_health(&pool, 1, |health| health.record_check(Some(100), 100, Duration::from_millis(200)));
+        pool.select();
+        assert_eq!(pool.current().url, "http://127.0.0.1:2");
+    }
+}
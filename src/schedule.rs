@@ -0,0 +1,204 @@
+use {
+    std::str::FromStr,
+    chrono::{prelude::*, Duration, Months},
+    solana_sdk::clock::UnixTimestamp,
+};
+
+/// How often the vaults of one mint are cranked.
+#[derive(Clone, Debug)]
+pub enum CrankSchedule {
+    /// Cron expression with a leading seconds field, e.g. `0 0 0 * * *` for daily at midnight UTC.
+    Cron(cron::Schedule),
+    /// ISO-8601 duration since the last accrual, e.g. `P1D` or `P1M`.
+    Interval(IsoInterval),
+}
+
+impl CrankSchedule {
+    /// First time after `last_accrued_at` the schedule allows the vault to be cranked again.
+    pub fn next_after(&self, last_accrued_at: UnixTimestamp) -> Option<UnixTimestamp> {
+        let last_accrued_at = Utc.timestamp_opt(last_accrued_at, 0).single()?;
+        let next = match self {
+            Self::Cron(schedule) => schedule.after(&last_accrued_at).next()?,
+            Self::Interval(interval) => interval.add_to(last_accrued_at)?,
+        };
+        Some(next.timestamp())
+    }
+}
+
+/// An ISO-8601 duration such as `P1M`, `P1W` or `PT12H`.
+///
+/// Years and months are calendar units, added with month-end clamping, while weeks and
+/// smaller units are exact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IsoInterval {
+    pub months: u32,
+    pub seconds: i64,
+}
+
+impl IsoInterval {
+    pub fn add_to(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        time.checked_add_months(Months::new(self.months))?
+            .checked_add_signed(Duration::seconds(self.seconds))
+    }
+}
+
+impl FromStr for IsoInterval {
+    type Err = String;
+
+    fn from_str(interval: &str) -> Result<Self, Self::Err> {
+        let rest = interval
+            .strip_prefix('P')
+            .ok_or_else(|| format!("{} must start with P", interval))?;
+        let (date, time) = match rest.split_once('T') {
+            Some((date, time)) if !time.is_empty() => (date, Some(time)),
+            Some(_) => return Err(format!("{} has no time components after T", interval)),
+            None => (rest, None),
+        };
+
+        let too_long = || format!("{} is too long", interval);
+        let mut months: u32 = 0;
+        let mut seconds: i64 = 0;
+        for (value, unit) in components(interval, date)? {
+            let (months_per_unit, seconds_per_unit) = match unit {
+                'Y' => (12, 0),
+                'M' => (1, 0),
+                'W' => (0, 7 * 24 * 60 * 60),
+                'D' => (0, 24 * 60 * 60),
+                _ => return Err(format!("{} has unknown date unit {}", interval, unit)),
+            };
+            let value_months = u32::try_from(value)
+                .ok()
+                .and_then(|value| value.checked_mul(months_per_unit))
+                .ok_or_else(too_long)?;
+            months = months.checked_add(value_months).ok_or_else(too_long)?;
+            seconds = value
+                .checked_mul(seconds_per_unit)
+                .and_then(|value_seconds| seconds.checked_add(value_seconds))
+                .ok_or_else(too_long)?;
+        }
+        for (value, unit) in components(interval, time.unwrap_or_default())? {
+            let seconds_per_unit = match unit {
+                'H' => 60 * 60,
+                'M' => 60,
+                'S' => 1,
+                _ => return Err(format!("{} has unknown time unit {}", interval, unit)),
+            };
+            seconds = value
+                .checked_mul(seconds_per_unit)
+                .and_then(|value_seconds| seconds.checked_add(value_seconds))
+                .ok_or_else(too_long)?;
+        }
+
+        if months == 0 && seconds == 0 {
+            return Err(format!("{} must be longer than zero", interval));
+        }
+        Ok(Self { months, seconds })
+    }
+}
+
+/// Splits `1Y2M` style components into `(1, 'Y'), (2, 'M')`.
+fn components(interval: &str, part: &str) -> Result<Vec<(i64, char)>, String> {
+    let mut components = Vec::new();
+    let mut digits = String::new();
+    for c in part.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+        } else {
+            if digits.is_empty() {
+                return Err(format!("{} is missing a number before {}", interval, c));
+            }
+            let value = digits.parse().map_err(|_| format!("{} is too long", interval))?;
+            components.push((value, c));
+            digits.clear();
+        }
+    }
+    if !digits.is_empty() {
+        return Err(format!("{} ends with a number without a unit", interval));
+    }
+    Ok(components)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamp(rfc3339: &str) -> UnixTimestamp {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().timestamp()
+    }
+
+    fn interval(interval: &str) -> CrankSchedule {
+        CrankSchedule::Interval(interval.parse().unwrap())
+    }
+
+    #[test]
+    fn parses_date_and_time_components() {
+        assert_eq!(
+            "P1Y2M".parse::<IsoInterval>(),
+            Ok(IsoInterval { months: 14, seconds: 0 })
+        );
+        assert_eq!(
+            "P1DT12H".parse::<IsoInterval>(),
+            Ok(IsoInterval { months: 0, seconds: 36 * 60 * 60 })
+        );
+        assert_eq!(
+            "PT1H30M15S".parse::<IsoInterval>(),
+            Ok(IsoInterval { months: 0, seconds: 5415 })
+        );
+        assert_eq!(
+            "P1W".parse::<IsoInterval>(),
+            Ok(IsoInterval { months: 0, seconds: 7 * 24 * 60 * 60 })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_intervals() {
+        for invalid in ["", "1D", "P", "PT", "P0D", "PT0S", "P1X", "PT1D", "PD", "P1", "P1DT"] {
+            assert!(invalid.parse::<IsoInterval>().is_err(), "{} should not parse", invalid);
+        }
+    }
+
+    #[test]
+    fn rejects_intervals_that_overflow() {
+        for invalid in ["P5000000000M", "P400000000Y", "P99999999999999999999D", "PT9223372036854775807H"] {
+            assert!(invalid.parse::<IsoInterval>().is_err(), "{} should not parse", invalid);
+        }
+    }
+
+    #[test]
+    fn month_intervals_clamp_to_the_end_of_the_month() {
+        let schedule = interval("P1M");
+        assert_eq!(
+            schedule.next_after(timestamp("2023-01-31T10:00:00Z")),
+            Some(timestamp("2023-02-28T10:00:00Z"))
+        );
+        assert_eq!(
+            schedule.next_after(timestamp("2024-01-31T10:00:00Z")),
+            Some(timestamp("2024-02-29T10:00:00Z"))
+        );
+        assert_eq!(
+            schedule.next_after(timestamp("2023-03-15T00:00:00Z")),
+            Some(timestamp("2023-04-15T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn exact_intervals_add_seconds() {
+        assert_eq!(
+            interval("P1DT12H").next_after(timestamp("2023-01-01T00:00:00Z")),
+            Some(timestamp("2023-01-02T12:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn cron_fires_after_the_last_accrual() {
+        let schedule = CrankSchedule::Cron(cron::Schedule::from_str("0 0 0 * * *").unwrap());
+        assert_eq!(
+            schedule.next_after(timestamp("2023-01-01T00:00:00Z")),
+            Some(timestamp("2023-01-02T00:00:00Z"))
+        );
+        assert_eq!(
+            schedule.next_after(timestamp("2023-01-01T13:45:00Z")),
+            Some(timestamp("2023-01-02T00:00:00Z"))
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use {
+    std::{
+        cmp::Reverse,
+        collections::{BinaryHeap, HashMap, HashSet},
+        panic::AssertUnwindSafe,
+        sync::Arc,
+        time::Duration,
+    },
+    solana_sdk::pubkey::Pubkey,
+    futures::FutureExt,
+    tokio::{
+        sync::Semaphore,
+        task::JoinSet,
+        time::{sleep_until, Instant},
+    },
+    anyhow::Result,
+    tracing::{error, warn},
+    crate::{
+        alert::{Alert, AlertKind, Severity},
+        balance::BalanceStatus,
+        client::{redact_url, redact_urls},
+        cranker::Cranker,
+        discovery::DiscoveredVault,
+        error::CrankError,
+        outcome::CrankOutcome,
+        solvency::TreasuryCheck,
+        store::CrankStore,
+        vault_state::fetch_clock,
+    },
+};
+
+/// Savings vaults a crank task was given, and its outcomes unless it panicked.
+type CrankTaskResult = (Vec<Pubkey>, std::thread::Result<Vec<(DiscoveredVault, CrankOutcome)>>);
+
+/// Cranks every savings vault as soon as it is due, running up to `concurrency` batches at once.
+///
+/// Vaults wait in a priority queue keyed by their next due time, see `Cranker::next_due`. The queue is rebuilt from
+/// discovery and the on-chain schedules every `poll_interval`, which also picks up vaults whose
+/// crank failed. Vaults failing with a retryable error are queued again after `retry.max_delay`.
+pub struct Scheduler {
+    cranker: Arc<Cranker>,
+    store: Arc<CrankStore>,
+    queue: BinaryHeap<Reverse<(Instant, Pubkey)>>,
+    queued: HashMap<Pubkey, DiscoveredVault>,
+    in_flight: HashSet<Pubkey>,
+    /// Treasury checks over every vault due before the next refresh, shared by all batches so a
+    /// mint's treasury is not committed once per batch.
+    solvency: Arc<HashMap<Pubkey, TreasuryCheck>>,
+    /// Set while the cranker balance is below its floor, checked before every dispatch; no batches
+    /// are started until a refresh finds it recovered.
+    paused: bool,
+}
+
+impl Scheduler {
+    pub fn new(cranker: Arc<Cranker>, store: Arc<CrankStore>) -> Self {
+        Self {
+            cranker,
+            store,
+            queue: BinaryHeap::new(),
+            queued: HashMap::new(),
+            in_flight: HashSet::new(),
+            solvency: Arc::new(HashMap::new()),
+            paused: false,
+        }
+    }
+
+    /// Runs until the process exits, passing every crank outcome to `report`.
+    pub async fn run<F>(mut self, mut report: F) -> Result<()>
+    where
+        F: FnMut(&DiscoveredVault, &CrankOutcome),
+    {
+        let config = self.cranker.config().clone();
+        let semaphore = Arc::new(Semaphore::new(config.concurrency));
+        let mut tasks: JoinSet<CrankTaskResult> = JoinSet::new();
+        let mut next_refresh = Instant::now();
+
+        // Health checks only matter when there is another endpoint to fail over to.
+        if self.cranker.rpc_pool().endpoints().len() > 1 {
+            let cranker = self.cranker.clone();
+            tokio::spawn(async move {
+                loop {
+                    cranker.rpc_pool().check_health().await;
+                    tokio::time::sleep(cranker.config().rpc_health_check_interval).await;
+                }
+            });
+        }
+
+        loop {
+            if Instant::now() >= next_refresh {
+                next_refresh = Instant::now() + config.poll_interval;
+                if let Err(err) = self.refresh().await {
+                    // Providers often put the API key in the RPC URL, which must not reach a chat channel.
+                    let error = redact_urls(&format!("{:#}", err));
+                    warn!(error = %error, "failed to refresh savings vault schedule");
+                    self.cranker.alerts().alert(Alert {
+                        kind: AlertKind::RpcOutage,
+                        severity: Severity::Critical,
+                        subject: redact_url(&self.cranker.rpc_pool().current().url),
+                        message: format!("failed to refresh savings vault schedule: {}", error),
+                    });
+                    // Waiting a whole poll interval would leave nothing cranked after a failed startup.
+                    next_refresh = Instant::now() + config.retry.max_delay.min(config.poll_interval);
+                }
+            }
+
+            let batch_due = self.next_due().map_or(false, |due| due <= Instant::now());
+            if !self.paused && batch_due && semaphore.available_permits() > 0 {
+                self.paused = self.below_floor().await;
+            }
+            while !self.paused && semaphore.available_permits() > 0 {
+                let vaults = self.pop_due(config.max_batch_size);
+                if vaults.is_empty() {
+                    break;
+                }
+                let permit = semaphore.clone().acquire_owned().await?;
+                let cranker = self.cranker.clone();
+                let store = self.store.clone();
+                let solvency = self.solvency.clone();
+                let savings_vaults: Vec<Pubkey> = vaults.iter().map(|vault| vault.savings_vault).collect();
+                self.in_flight.extend(savings_vaults.iter().copied());
+                tasks.spawn(async move {
+                    let _permit = permit;
+                    let crank = async {
+                        let outcomes = cranker.crank_accrue_interest_batched(vaults, Some(&solvency)).await;
+                        cranker.record_outcomes(&store, &outcomes).await;
+                        outcomes
+                    };
+                    // A panic must not leave the vaults in flight forever, see the join below.
+                    (savings_vaults, AssertUnwindSafe(crank).catch_unwind().await)
+                });
+            }
+
+            let wake_at = match self.next_due() {
+                Some(due) if !self.paused && semaphore.available_permits() > 0 => due.min(next_refresh),
+                _ => next_refresh,
+            };
+            tokio::select! {
+                Some(joined) = tasks.join_next() => {
+                    match joined {
+                        Ok((savings_vaults, result)) => {
+                            for savings_vault in &savings_vaults {
+                                self.in_flight.remove(savings_vault);
+                            }
+                            match result {
+                                Ok(outcomes) => {
+                                    for (vault, outcome) in outcomes {
+                                        report(&vault, &outcome);
+                                        if outcome.error().map_or(false, CrankError::is_retryable) {
+                                            self.requeue(vault, config.retry.max_delay);
+                                        }
+                                    }
+                                }
+                                // The next refresh queues the vaults again.
+                                Err(_) => error!(vaults = savings_vaults.len(), "crank task panicked"),
+                            }
+                        }
+                        Err(err) => error!(error = %err, "crank task failed"),
+                    }
+                }
+                _ = sleep_until(wake_at) => {}
+            }
+        }
+    }
+
+    /// Rebuilds the queue from the discovered vaults, translating each on-chain due time, or the
+    /// later one implied by the crank state store, into a local deadline through the cluster's
+    /// current clock. Also checks the treasuries against every vault due before the next refresh
+    /// and pauses cranking while the cranker balance is below its floor.
+    async fn refresh(&mut self) -> Result<()> {
+        let mut vaults = self.cranker.discover().await?;
+        for vault in &mut vaults {
+            self.cranker.apply_recorded_accrual(&self.store, vault);
+        }
+        let chain_now = fetch_clock(self.cranker.rpc()).await?.unix_timestamp;
+        let now = Instant::now();
+
+        // A failed balance lookup keeps the previous decision rather than pausing on an RPC hiccup.
+        match self.cranker.check_balance().await {
+            Ok(report) => self.paused = report.status == BalanceStatus::BelowFloor,
+            Err(err) => warn!(error = format!("{:#}", err), "failed to check cranker balance"),
+        }
+
+        self.queue.clear();
+        self.queued.clear();
+        let mut vault_counts: HashMap<Pubkey, (i64, i64)> = HashMap::new();
+        let refresh_at = chain_now.saturating_add(self.cranker.config().poll_interval.as_secs() as i64);
+        let mut due_before_refresh = Vec::new();
+        for vault in vaults {
+            let next_due = self.cranker.next_due(&vault);
+            if next_due <= refresh_at {
+                due_before_refresh.push(vault);
+            }
+            let counts = vault_counts.entry(vault.mint).or_default();
+            counts.0 += 1;
+            if next_due <= chain_now {
+                counts.1 += 1;
+            }
+            if self.in_flight.contains(&vault.savings_vault) {
+                continue;
+            }
+            let due_in = next_due.saturating_sub(chain_now).max(0);
+            self.queue.push(Reverse((now + Duration::from_secs(due_in as u64), vault.savings_vault)));
+            self.queued.insert(vault.savings_vault, vault);
+        }
+        self.cranker
+            .metrics()
+            .set_vault_counts(vault_counts.iter().map(|(mint, counts)| (mint, *counts)));
+
+        // Without the checks every batch is sent and the program remains the judge.
+        self.solvency = Arc::new(match self.cranker.check_solvency(&due_before_refresh).await {
+            Ok(checks) => checks,
+            Err(err) => {
+                warn!(error = format!("{:#}", err), "failed to check interest depositor treasuries");
+                HashMap::new()
+            }
+        });
+        Ok(())
+    }
+
+    /// Re-checks the balance floor before batches are started, so cranking pauses as soon as fees
+    /// drain the balance rather than at the next refresh. Only `refresh` resumes.
+    async fn below_floor(&self) -> bool {
+        match self.cranker.balance_status().await {
+            Ok(BalanceStatus::BelowFloor) => {
+                // Logs and alerts the drop below the floor.
+                if let Err(err) = self.cranker.check_balance().await {
+                    warn!(error = format!("{:#}", err), "failed to check cranker balance");
+                }
+                true
+            }
+            Ok(_) => false,
+            Err(err) => {
+                warn!(error = format!("{:#}", err), "failed to check cranker balance");
+                false
+            }
+        }
+    }
+
+    /// Queues `vault` again after `delay` unless a refresh has already queued it.
+    fn requeue(&mut self, vault: DiscoveredVault, delay: Duration) {
+        if self.queued.contains_key(&vault.savings_vault) {
+            return;
+        }
+        self.queue.push(Reverse((Instant::now() + delay, vault.savings_vault)));
+        self.queued.insert(vault.savings_vault, vault);
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.queue.peek().map(|Reverse((due, _))| *due)
+    }
+
+    /// Pops up to `limit` vaults whose due time has passed.
+    fn pop_due(&mut self, limit: usize) -> Vec<DiscoveredVault> {
+        let now = Instant::now();
+        let mut vaults = Vec::new();
+        while vaults.len() < limit {
+            match self.queue.peek() {
+                Some(Reverse((due, _))) if *due <= now => {}
+                _ => break,
+            }
+            if let Some(Reverse((_, savings_vault))) = self.queue.pop() {
+                if let Some(vault) = self.queued.remove(&savings_vault) {
+                    vaults.push(vault);
+                }
+            }
+        }
+        vaults
+    }
+}
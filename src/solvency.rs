@@ -0,0 +1,79 @@
+use {
+    std::collections::HashMap,
+    solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey},
+    spl_token::state::Account as TokenAccount,
+    crate::{discovery::DiscoveredVault, pda::VaultPdas},
+};
+
+/// Denominator of `DiscoveredVault::interest_rate_bps`.
+pub const INTEREST_RATE_DENOMINATOR: u64 = 10_000;
+
+/// Interest one accrual pays on `principal` at `interest_rate_bps` per period, rounded up so the
+/// estimate never understates what the treasury must hold.
+pub fn interest_owed(principal: u64, interest_rate_bps: u64) -> u64 {
+    let owed = (u128::from(principal) * u128::from(interest_rate_bps) + u128::from(INTEREST_RATE_DENOMINATOR) - 1)
+        / u128::from(INTEREST_RATE_DENOMINATOR);
+    u64::try_from(owed).unwrap_or(u64::MAX)
+}
+
+/// Whether the interest depositor treasury of a mint can cover the next accrual of its due vaults.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TreasuryCheck {
+    pub mint: Pubkey,
+    pub interest_depositor_treasury: Pubkey,
+    /// Token balance of the treasury, zero when the account does not exist.
+    pub balance: u64,
+    /// Estimated interest owed by the due vaults of the mint.
+    pub owed: u64,
+}
+
+impl TreasuryCheck {
+    pub fn is_underfunded(&self) -> bool {
+        self.balance < self.owed
+    }
+}
+
+/// Estimates per mint whether the interest depositor treasury covers the interest `vaults` owe,
+/// from the treasury accounts read by `preflight::fetch_vault_accounts`.
+pub fn check_treasuries(
+    program_id: &Pubkey,
+    vaults: &[DiscoveredVault],
+    accounts: &HashMap<Pubkey, Account>,
+) -> HashMap<Pubkey, TreasuryCheck> {
+    let pdas: Vec<VaultPdas> = vaults
+        .iter()
+        .map(|vault| VaultPdas::derive(program_id, &vault.wallet, &vault.mint))
+        .collect();
+    let mut interest_depositor_treasuries: HashMap<Pubkey, Pubkey> = HashMap::new();
+    for (vault, pdas) in vaults.iter().zip(&pdas) {
+        interest_depositor_treasuries.insert(vault.mint, pdas.interest_depositor_treasury);
+    }
+
+    let mut checks: HashMap<Pubkey, TreasuryCheck> = interest_depositor_treasuries
+        .into_iter()
+        .map(|(mint, treasury)| {
+            let check = TreasuryCheck {
+                mint,
+                interest_depositor_treasury: treasury,
+                balance: token_balance(accounts.get(&treasury)),
+                owed: 0,
+            };
+            (mint, check)
+        })
+        .collect();
+    for (vault, pdas) in vaults.iter().zip(&pdas) {
+        if let Some(check) = checks.get_mut(&vault.mint) {
+            let principal = token_balance(accounts.get(&pdas.savings_vault_treasury));
+            check.owed = check.owed.saturating_add(interest_owed(principal, vault.interest_rate_bps));
+        }
+    }
+
+    checks
+}
+
+/// Token balance of `account`, zero when it is missing or not a token account.
+pub fn token_balance(account: Option<&Account>) -> u64 {
+    account
+        .and_then(|account| TokenAccount::unpack(&account.data).ok())
+        .map_or(0, |token_account| token_account.amount)
+}
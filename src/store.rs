@@ -0,0 +1,110 @@
+use {
+    std::{
+        path::Path,
+        str::FromStr,
+        sync::{Mutex, MutexGuard},
+    },
+    chrono::prelude::*,
+    rusqlite::{params, Connection, OptionalExtension},
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
+    anyhow::{anyhow, Result},
+};
+
+/// Last successful `AccrueInterest` for a single savings vault.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccrualRecord {
+    pub savings_vault: Pubkey,
+    pub wallet: Pubkey,
+    pub mint: Pubkey,
+    pub last_accrued_at: DateTime<Utc>,
+    pub signature: Signature,
+    pub slot: u64,
+}
+
+/// SQLite backed crank history, keyed by savings vault PDA so it survives restarts.
+///
+/// The connection sits behind a mutex so concurrent crank tasks can share one store.
+pub struct CrankStore {
+    conn: Mutex<Connection>,
+}
+
+impl CrankStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS accruals (
+                savings_vault   TEXT PRIMARY KEY NOT NULL,
+                wallet          TEXT NOT NULL,
+                mint            TEXT NOT NULL,
+                last_accrued_at INTEGER NOT NULL,
+                signature       TEXT NOT NULL,
+                slot            INTEGER NOT NULL
+            );",
+        )?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    pub fn last_accrual(&self, savings_vault: &Pubkey) -> Result<Option<AccrualRecord>> {
+        let row = self
+            .connection()?
+            .query_row(
+                "SELECT wallet, mint, last_accrued_at, signature, slot
+                 FROM accruals WHERE savings_vault = ?1",
+                params![savings_vault.to_string()],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, i64>(4)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        let (wallet, mint, last_accrued_at, signature, slot) = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let last_accrued_at = Utc
+            .timestamp_opt(last_accrued_at, 0)
+            .single()
+            .ok_or_else(|| anyhow!("Invalid accrual timestamp {} for {}", last_accrued_at, savings_vault))?;
+
+        Ok(Some(AccrualRecord {
+            savings_vault: *savings_vault,
+            wallet: Pubkey::from_str(&wallet)?,
+            mint: Pubkey::from_str(&mint)?,
+            last_accrued_at,
+            signature: Signature::from_str(&signature)?,
+            slot: slot as u64,
+        }))
+    }
+
+    pub fn record_accrual(&self, record: &AccrualRecord) -> Result<()> {
+        self.connection()?.execute(
+            "INSERT INTO accruals (savings_vault, wallet, mint, last_accrued_at, signature, slot)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(savings_vault) DO UPDATE SET
+                wallet = excluded.wallet,
+                mint = excluded.mint,
+                last_accrued_at = excluded.last_accrued_at,
+                signature = excluded.signature,
+                slot = excluded.slot",
+            params![
+                record.savings_vault.to_string(),
+                record.wallet.to_string(),
+                record.mint.to_string(),
+                record.last_accrued_at.timestamp(),
+                record.signature.to_string(),
+                record.slot as i64,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| anyhow!("Crank state store lock poisoned"))
+    }
+}
@@ -0,0 +1,270 @@
+use {
+    std::time::{Duration, Instant},
+    solana_program::sysvar,
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::{Keypair, Signature},
+        signer::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+    solana_client::{
+        client_error::ClientError,
+        nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+        rpc_config::{
+            RpcSendTransactionConfig, RpcSignatureSubscribeConfig, RpcSimulateTransactionConfig, RpcTransactionConfig,
+        },
+        rpc_response::{ProcessedSignatureResult, Response, RpcSignatureResult, RpcSimulateTransactionResult},
+    },
+    futures::{stream::BoxStream, StreamExt},
+    anchor_client::anchor_lang::{InstructionData, ToAccountMetas},
+    anyhow::Result,
+    savings_vault::{accounts, instruction},
+    spl_token::ID as TOKEN_PROGRAM_ID,
+    crate::{config::CrankConfig, error::CrankError, outcome::CrankOutcome, pda::VaultPdas},
+};
+
+/// How often the status of a sent accrue transaction is polled while confirming it.
+pub const CONFIRM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often the status is still polled while a websocket subscription reports it, in case a
+/// notification is lost.
+pub const SUBSCRIBED_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long connecting and subscribing to the websocket endpoint may take before confirmation
+/// falls back to polling.
+pub const WS_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a failed transaction is looked up for its logs before classifying it without them.
+const TRANSACTION_LOG_POLLS: u32 = 10;
+
+/// Builds the `AccrueInterest` instruction for the savings vault of `wallet` and `mint`.
+pub fn accrue_interest_instruction(
+    program_id: &Pubkey,
+    cranker: &Pubkey,
+    wallet: &Pubkey,
+    mint: &Pubkey,
+) -> Instruction {
+    let pdas = VaultPdas::derive(program_id, wallet, mint);
+
+    Instruction {
+        program_id: *program_id,
+        accounts: accounts::AccrueInterest {
+            mint: *mint,
+            cranker: *cranker,
+            wallet: *wallet,
+            savings_vault: pdas.savings_vault,
+            savings_vault_treasury: pdas.savings_vault_treasury,
+            interest_depositor_manager: pdas.interest_depositor_manager,
+            interest_depositor_treasury: pdas.interest_depositor_treasury,
+            token_program: TOKEN_PROGRAM_ID,
+            clock: sysvar::clock::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::AccrueInterest {}.data(),
+    }
+}
+
+/// Simulates `instructions` paid by `payer` without signing them, against the latest blockhash.
+pub async fn simulate(
+    rpc: &RpcClient,
+    commitment: CommitmentConfig,
+    payer: &Pubkey,
+    instructions: &[Instruction],
+) -> Result<RpcSimulateTransactionResult, ClientError> {
+    let transaction = Transaction::new_with_payer(instructions, Some(payer));
+    let simulate_config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(commitment),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let Response { value, .. } = rpc.simulate_transaction_with_config(&transaction, simulate_config).await?;
+
+    Ok(value)
+}
+
+/// Result of simulating a batch before it is sent.
+#[derive(Clone, Debug)]
+pub enum BatchSimulation {
+    Succeeded { units_consumed: Option<u64> },
+    Failed { error: TransactionError, logs: Vec<String> },
+    /// The simulation request itself failed, the batch is sent with the fallback compute limit.
+    Unavailable,
+}
+
+impl From<RpcSimulateTransactionResult> for BatchSimulation {
+    fn from(result: RpcSimulateTransactionResult) -> Self {
+        match result.err {
+            Some(error) => Self::Failed {
+                error,
+                logs: result.logs.unwrap_or_default(),
+            },
+            None => Self::Succeeded {
+                units_consumed: result.units_consumed,
+            },
+        }
+    }
+}
+
+/// Signs `instructions` against a freshly fetched blockhash, sends them and waits for confirmation
+/// through a signature subscription on `ws_url`.
+///
+/// Every call re-signs, so a retry after an expired blockhash produces a new transaction. A
+/// previous attempt that still lands afterwards makes the retry fail as already accrued.
+pub async fn send_and_confirm(
+    rpc: &RpcClient,
+    ws_url: &str,
+    config: &CrankConfig,
+    cranker: &Keypair,
+    instructions: &[Instruction],
+) -> CrankOutcome {
+    let mut transaction = Transaction::new_with_payer(instructions, Some(&cranker.pubkey()));
+    let recent_blockhash = match rpc.get_latest_blockhash().await {
+        Ok(recent_blockhash) => recent_blockhash,
+        Err(err) => return CrankOutcome::rpc_failure(&err),
+    };
+    if let Err(err) = transaction.try_sign(&[cranker], recent_blockhash) {
+        return CrankOutcome::failed(None, CrankError::SignerError(err.to_string()));
+    }
+
+    let send_config = RpcSendTransactionConfig {
+        preflight_commitment: Some(config.commitment.commitment),
+        ..RpcSendTransactionConfig::default()
+    };
+    match rpc.send_transaction_with_config(&transaction, send_config).await {
+        Ok(signature) => {
+            confirm_transaction(rpc, Some(ws_url), signature, config.commitment, config.confirm_timeout).await
+        }
+        Err(err) => CrankOutcome::from_client_error(&err),
+    }
+}
+
+/// Waits until `signature` reaches `commitment`, fails, or `timeout` elapses.
+///
+/// With `ws_url` the signature is subscribed to and the status polled only as a backstop;
+/// without it, or when the subscription cannot be set up, the status is polled throughout.
+pub async fn confirm_transaction(
+    rpc: &RpcClient,
+    ws_url: Option<&str>,
+    signature: Signature,
+    commitment: CommitmentConfig,
+    timeout: Duration,
+) -> CrankOutcome {
+    let pubsub = match ws_url {
+        Some(ws_url) => match tokio::time::timeout(WS_CONNECT_TIMEOUT, PubsubClient::new(ws_url)).await {
+            Ok(Ok(pubsub)) => Some(pubsub),
+            _ => None,
+        },
+        None => None,
+    };
+    let subscribe_config = RpcSignatureSubscribeConfig {
+        commitment: Some(commitment),
+        enable_received_notification: Some(false),
+    };
+    let outcome = match &pubsub {
+        Some(pubsub) => {
+            let subscription =
+                tokio::time::timeout(WS_CONNECT_TIMEOUT, pubsub.signature_subscribe(&signature, Some(subscribe_config)))
+                    .await;
+            match subscription {
+                Ok(Ok((notifications, unsubscribe))) => {
+                    let outcome = wait_for_signature(rpc, signature, commitment, timeout, Some(notifications)).await;
+                    unsubscribe().await;
+                    outcome
+                }
+                _ => wait_for_signature(rpc, signature, commitment, timeout, None).await,
+            }
+        }
+        None => wait_for_signature(rpc, signature, commitment, timeout, None).await,
+    };
+    if let Some(pubsub) = pubsub {
+        let _ = pubsub.shutdown().await;
+    }
+    outcome
+}
+
+/// Polls the status of `signature`, waiting on `notifications` between polls when subscribed.
+async fn wait_for_signature(
+    rpc: &RpcClient,
+    signature: Signature,
+    commitment: CommitmentConfig,
+    timeout: Duration,
+    mut notifications: Option<BoxStream<'_, Response<RpcSignatureResult>>>,
+) -> CrankOutcome {
+    let started = Instant::now();
+    loop {
+        // Status lookups are retried until the timeout, a single failed poll says nothing about the transaction.
+        if let Ok(Response { value, .. }) = rpc.get_signature_statuses(&[signature]).await {
+            if let Some(Some(status)) = value.into_iter().next() {
+                if let Some(error) = status.err.clone() {
+                    let logs = transaction_logs(rpc, &signature, commitment).await;
+                    return CrankOutcome::from_transaction_error(Some(signature), error, &logs);
+                }
+                if status.satisfies_commitment(commitment) {
+                    return CrankOutcome::Success {
+                        signature,
+                        slot: status.slot,
+                    };
+                }
+            }
+        }
+        let remaining = match timeout.checked_sub(started.elapsed()) {
+            Some(remaining) if !remaining.is_zero() => remaining,
+            _ => return CrankOutcome::failed(Some(signature), CrankError::Timeout),
+        };
+        let stream = match notifications.as_mut() {
+            Some(stream) => stream,
+            None => {
+                tokio::time::sleep(CONFIRM_POLL_INTERVAL.min(remaining)).await;
+                continue;
+            }
+        };
+        match tokio::time::timeout(SUBSCRIBED_POLL_INTERVAL.min(remaining), stream.next()).await {
+            Ok(Some(Response { context, value })) => match value {
+                RpcSignatureResult::ProcessedSignature(ProcessedSignatureResult { err: Some(error) }) => {
+                    let logs = transaction_logs(rpc, &signature, commitment).await;
+                    return CrankOutcome::from_transaction_error(Some(signature), error, &logs);
+                }
+                RpcSignatureResult::ProcessedSignature(_) => {
+                    return CrankOutcome::Success {
+                        signature,
+                        slot: context.slot,
+                    };
+                }
+                RpcSignatureResult::ReceivedSignature(_) => {}
+            },
+            // The subscription closed, keep polling on the usual interval.
+            Ok(None) => notifications = None,
+            Err(_) => {}
+        }
+    }
+}
+
+/// Program logs of the landed transaction `signature`, so its Anchor error can be named. Empty
+/// when the transaction cannot be fetched within a few polls.
+async fn transaction_logs(rpc: &RpcClient, signature: &Signature, commitment: CommitmentConfig) -> Vec<String> {
+    // getTransaction does not serve processed transactions.
+    let commitment = if commitment.is_finalized() {
+        commitment
+    } else {
+        CommitmentConfig::confirmed()
+    };
+    let config = RpcTransactionConfig {
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+        ..RpcTransactionConfig::default()
+    };
+    for _ in 0..TRANSACTION_LOG_POLLS {
+        if let Ok(transaction) = rpc.get_transaction_with_config(signature, config).await {
+            return transaction
+                .transaction
+                .meta
+                .and_then(|meta| Option::<Vec<String>>::from(meta.log_messages))
+                .unwrap_or_default();
+        }
+        tokio::time::sleep(CONFIRM_POLL_INTERVAL).await;
+    }
+    Vec::new()
+}
@@ -0,0 +1,60 @@
+use {
+    solana_program::sysvar,
+    solana_sdk::{clock::{Clock, UnixTimestamp}, pubkey::Pubkey},
+    solana_client::nonblocking::rpc_client::RpcClient,
+    anchor_client::anchor_lang::AccountDeserialize,
+    anyhow::{anyhow, Result},
+    savings_vault::state::SavingsVault,
+};
+
+/// When a savings vault last accrued interest and how long it waits between accruals, as
+/// recorded in its on-chain account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VaultSchedule {
+    pub last_accrued_at: UnixTimestamp,
+    /// Seconds between two accruals, zero when the vault does not set one.
+    pub interest_period: i64,
+}
+
+impl VaultSchedule {
+    pub fn from_account(state: &SavingsVault) -> Self {
+        Self {
+            last_accrued_at: state.last_accrual_timestamp,
+            interest_period: state.interest_period,
+        }
+    }
+
+    /// Unix time, on the cluster's clock, from which the program lets the vault accrue again,
+    /// `None` when the account does not carry an interest period.
+    pub fn period_due(&self) -> Option<UnixTimestamp> {
+        (self.interest_period > 0).then(|| self.last_accrued_at.saturating_add(self.interest_period))
+    }
+
+    /// Like `period_due`, using `fallback_period` for vaults without an interest period.
+    pub fn next_due(&self, fallback_period: i64) -> UnixTimestamp {
+        self.period_due()
+            .unwrap_or_else(|| self.last_accrued_at.saturating_add(fallback_period))
+    }
+}
+
+/// Interest, in basis points of the savings vault treasury balance, paid on every accrual.
+pub fn interest_rate_bps(state: &SavingsVault) -> u64 {
+    state.interest_rate_bps.into()
+}
+
+/// Reads and deserializes a savings vault account, `None` when it does not exist.
+pub async fn fetch_savings_vault(rpc: &RpcClient, savings_vault: &Pubkey) -> Result<Option<SavingsVault>> {
+    let account = match rpc.get_account_with_commitment(savings_vault, rpc.commitment()).await?.value {
+        Some(account) => account,
+        None => return Ok(None),
+    };
+
+    Ok(Some(SavingsVault::try_deserialize(&mut account.data.as_slice())?))
+}
+
+/// Reads the `Clock` sysvar so due times are compared against the cluster's time.
+pub async fn fetch_clock(rpc: &RpcClient) -> Result<Clock> {
+    let account = rpc.get_account(&sysvar::clock::ID).await?;
+
+    bincode::deserialize(&account.data).map_err(|err| anyhow!("Failed to deserialize clock sysvar: {}", err))
+}